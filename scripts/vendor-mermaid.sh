@@ -0,0 +1,17 @@
+#!/bin/sh
+# Copy the mermaid runtime into the default template's `_static/mermaid`, to be
+# embedded in the binary and loaded by pages with diagrams.
+set -eu
+
+MERMAID_VERSION="${MERMAID_VERSION:-10.9.1}"
+TARGET="$(dirname "$0")/../templates/default/_static/mermaid"
+WORKDIR="$(mktemp -d)"
+trap 'rm -rf "$WORKDIR"' EXIT
+
+(cd "$WORKDIR" && npm pack --silent "mermaid@$MERMAID_VERSION" >/dev/null && tar -xzf "mermaid-$MERMAID_VERSION.tgz")
+
+rm -rf "$TARGET"
+mkdir -p "$TARGET/chunks"
+cp "$WORKDIR/package/dist/mermaid.esm.min.mjs" "$TARGET/"
+cp -r "$WORKDIR/package/dist/chunks/mermaid.esm.min" "$TARGET/chunks/"
+cp "$WORKDIR/package/LICENSE" "$TARGET/"
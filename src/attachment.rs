@@ -0,0 +1,149 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// How files of a type are embedded with `![[file]]`.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum EmbedKind {
+    Image,
+    Audio,
+    Video,
+    Pdf,
+    /// A link to download the file.
+    #[default]
+    Download,
+}
+
+/// A type of attachment, by file extension.
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+#[serde(default)]
+pub struct FileType {
+    pub embed: EmbedKind,
+
+    /// MIME type of the files.
+    pub mime: Option<String>,
+
+    /// HTML written for embeds instead of the `embed` kind's. `{url}`,
+    /// `{name}` and `{mime}` are replaced with the file's.
+    pub html: Option<String>,
+}
+
+impl FileType {
+    fn builtin(embed: EmbedKind, mime: &str) -> Self {
+        Self {
+            embed,
+            mime: Some(mime.to_string()),
+            html: None,
+        }
+    }
+}
+
+const BUILTIN_FILE_TYPES: &[(&str, EmbedKind, &str)] = &[
+    ("png", EmbedKind::Image, "image/png"),
+    ("webp", EmbedKind::Image, "image/webp"),
+    ("jpg", EmbedKind::Image, "image/jpeg"),
+    ("jpeg", EmbedKind::Image, "image/jpeg"),
+    ("gif", EmbedKind::Image, "image/gif"),
+    ("bmp", EmbedKind::Image, "image/bmp"),
+    ("svg", EmbedKind::Image, "image/svg+xml"),
+    ("avif", EmbedKind::Image, "image/avif"),
+    ("mp3", EmbedKind::Audio, "audio/mpeg"),
+    ("webm", EmbedKind::Audio, "audio/webm"),
+    ("wav", EmbedKind::Audio, "audio/wav"),
+    ("m4a", EmbedKind::Audio, "audio/mp4"),
+    ("ogg", EmbedKind::Audio, "audio/ogg"),
+    ("3gp", EmbedKind::Audio, "audio/3gpp"),
+    ("flac", EmbedKind::Audio, "audio/flac"),
+    ("mp4", EmbedKind::Video, "video/mp4"),
+    ("ogv", EmbedKind::Video, "video/ogg"),
+    ("mov", EmbedKind::Video, "video/quicktime"),
+    ("mkv", EmbedKind::Video, "video/x-matroska"),
+    ("pdf", EmbedKind::Pdf, "application/pdf"),
+    ("zip", EmbedKind::Download, "application/zip"),
+    ("csv", EmbedKind::Download, "text/csv"),
+    ("txt", EmbedKind::Download, "text/plain"),
+];
+
+/// Registry of attachment types: the built-in ones, with the `file_types`
+/// from `site.yaml` added over them.
+#[derive(Debug)]
+pub(crate) struct FileTypes {
+    by_extension: HashMap<String, FileType>,
+    fallback: FileType,
+}
+
+impl FileTypes {
+    pub fn new(custom: &HashMap<String, FileType>) -> Self {
+        let mut by_extension: HashMap<String, FileType> = BUILTIN_FILE_TYPES
+            .iter()
+            .map(|(extension, embed, mime)| {
+                (extension.to_string(), FileType::builtin(*embed, mime))
+            })
+            .collect();
+
+        for (extension, file_type) in custom.iter() {
+            let extension = extension.trim_start_matches('.').to_lowercase();
+            by_extension.insert(extension, file_type.clone());
+        }
+
+        Self {
+            by_extension,
+            fallback: FileType::default(),
+        }
+    }
+
+    /// Whether files with `extension` are of a built-in or custom type.
+    pub fn contains(&self, extension: &str) -> bool {
+        self.by_extension.contains_key(&extension.to_lowercase())
+    }
+
+    /// Type of files with `extension`, a download when it is not known.
+    pub fn get(&self, extension: &str) -> &FileType {
+        self.by_extension
+            .get(&extension.to_lowercase())
+            .unwrap_or(&self.fallback)
+    }
+}
+
+impl Default for FileTypes {
+    fn default() -> Self {
+        Self::new(&HashMap::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{EmbedKind, FileType, FileTypes};
+    use std::collections::HashMap;
+
+    #[test]
+    fn test_builtin_file_types() {
+        let file_types = FileTypes::default();
+        assert_eq!(file_types.get("PNG").embed, EmbedKind::Image);
+        assert_eq!(file_types.get("webm").embed, EmbedKind::Audio);
+        assert_eq!(file_types.get("csv").mime.as_deref(), Some("text/csv"));
+        assert_eq!(file_types.get("drawio"), &FileType::default());
+    }
+
+    #[test]
+    fn test_custom_file_types() {
+        let custom: HashMap<String, FileType> = serde_yaml::from_str(
+            ".drawio:\n  mime: application/xml\n  html: <iframe src=\"{url}\"></iframe>\n\
+            webm:\n  embed: video",
+        )
+        .unwrap();
+        let file_types = FileTypes::new(&custom);
+
+        assert_eq!(file_types.get("webm").embed, EmbedKind::Video);
+        assert_eq!(
+            file_types.get("drawio"),
+            &FileType {
+                embed: EmbedKind::Download,
+                mime: Some("application/xml".to_string()),
+                html: Some("<iframe src=\"{url}\"></iframe>".to_string()),
+            }
+        );
+        assert!(file_types.contains("DRAWIO"));
+        assert!(!file_types.contains("o"));
+    }
+}
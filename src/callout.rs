@@ -0,0 +1,301 @@
+use pulldown_cmark::{escape::escape_html, Event, Tag};
+use serde::Deserialize;
+use std::collections::HashMap;
+
+/// A callout type declared in `site.yaml` under `callouts`.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct CalloutType {
+    /// Title used when the callout does not define one.
+    pub title: Option<String>,
+
+    /// Text or HTML shown before the title.
+    pub icon: Option<String>,
+
+    /// CSS color, exposed to the stylesheet as `--callout-color`.
+    pub color: Option<String>,
+}
+
+/// Obsidian's built-in callout types as `(type, icon, aliases)`.
+const BUILTIN_CALLOUTS: &[(&str, &str, &[&str])] = &[
+    ("note", "✎", &[]),
+    ("abstract", "☰", &["summary", "tldr"]),
+    ("info", "ℹ", &[]),
+    ("todo", "☑", &[]),
+    ("tip", "✦", &["hint", "important"]),
+    ("success", "✔", &["check", "done"]),
+    ("question", "?", &["help", "faq"]),
+    ("warning", "⚠", &["caution", "attention"]),
+    ("failure", "✘", &["fail", "missing"]),
+    ("danger", "ϟ", &["error"]),
+    ("bug", "⚙", &[]),
+    ("example", "☷", &[]),
+    ("quote", "❝", &["cite"]),
+];
+
+#[derive(Debug, PartialEq)]
+enum Fold {
+    None,
+    Open,
+    Closed,
+}
+
+#[derive(Debug, PartialEq)]
+struct CalloutMarker {
+    kind: String,
+    fold: Fold,
+    title: String,
+}
+
+/// Turn blockquotes starting with `[!type]` into callouts.
+///
+/// The first line holds the type, an optional `+`/`-` fold marker and the
+/// title; the rest of the blockquote becomes the callout content.
+pub(crate) fn render_callouts<'a>(
+    events: Vec<Event<'a>>,
+    types: &HashMap<String, CalloutType>,
+) -> Vec<Event<'a>> {
+    let mut output = Vec::with_capacity(events.len());
+    // one entry per open blockquote: the closing markup if it is a callout
+    let mut stack: Vec<Option<&str>> = Vec::new();
+    let mut events = events.into_iter().peekable();
+
+    while let Some(event) = events.next() {
+        match event {
+            Event::Start(Tag::BlockQuote) => {
+                output.push(event);
+
+                if events.peek() != Some(&Event::Start(Tag::Paragraph)) {
+                    stack.push(None);
+                    continue;
+                }
+                let paragraph = events.next().unwrap();
+
+                let mut head = String::new();
+                while let Some(Event::Text(text)) = events.peek() {
+                    head.push_str(text);
+                    events.next();
+                }
+
+                let marker = match parse_marker(&head) {
+                    Some(marker) => marker,
+                    None => {
+                        stack.push(None);
+                        output.push(paragraph);
+                        if !head.is_empty() {
+                            output.push(Event::Text(head.into()));
+                        }
+                        continue;
+                    }
+                };
+
+                // drop the `Start(BlockQuote)` pushed above, the callout
+                // markup replaces it
+                output.pop();
+
+                let mut title = Vec::new();
+                if !marker.title.is_empty() {
+                    title.push(Event::Text(marker.title.clone().into()));
+                }
+
+                let mut paragraph_open = false;
+                for event in events.by_ref() {
+                    match event {
+                        Event::SoftBreak | Event::HardBreak => {
+                            paragraph_open = true;
+                            break;
+                        }
+                        Event::End(Tag::Paragraph) => break,
+                        event => title.push(event),
+                    }
+                }
+
+                let (open_html, default_title, close_html) = callout_html(&marker, types);
+                output.push(Event::Html(open_html.into()));
+                if title.is_empty() {
+                    output.push(Event::Text(default_title.into()));
+                } else {
+                    output.extend(title);
+                }
+                output.push(Event::Html(match marker.fold {
+                    Fold::None => "</span></div>\n<div class=\"callout-content\">\n".into(),
+                    _ => "</span></summary>\n<div class=\"callout-content\">\n".into(),
+                }));
+                if paragraph_open {
+                    output.push(Event::Start(Tag::Paragraph));
+                }
+
+                stack.push(Some(close_html));
+            }
+            Event::End(Tag::BlockQuote) => match stack.pop().flatten() {
+                Some(close_html) => output.push(Event::Html(close_html.into())),
+                None => output.push(event),
+            },
+            event => output.push(event),
+        }
+    }
+
+    output
+}
+
+fn parse_marker(head: &str) -> Option<CalloutMarker> {
+    let rest = head.strip_prefix("[!")?;
+    let end = rest.find(']')?;
+    let kind = &rest[..end];
+
+    if kind.is_empty()
+        || !kind
+            .chars()
+            .all(|c| c.is_alphanumeric() || c == '-' || c == '_')
+    {
+        return None;
+    }
+
+    let rest = &rest[end + 1..];
+    let (fold, rest) = match rest.chars().next() {
+        Some('+') => (Fold::Open, &rest[1..]),
+        Some('-') => (Fold::Closed, &rest[1..]),
+        _ => (Fold::None, rest),
+    };
+
+    Some(CalloutMarker {
+        kind: kind.to_lowercase(),
+        fold,
+        title: rest.trim().to_string(),
+    })
+}
+
+/// Opening markup up to the title text, the default title and the closing
+/// markup.
+fn callout_html(
+    marker: &CalloutMarker,
+    types: &HashMap<String, CalloutType>,
+) -> (String, String, &'static str) {
+    let (kind, icon, color, title) = match types.get(&marker.kind) {
+        Some(custom) => (
+            marker.kind.as_str(),
+            custom.icon.clone().unwrap_or_default(),
+            custom.color.clone(),
+            custom.title.clone(),
+        ),
+        None => {
+            let builtin = BUILTIN_CALLOUTS.iter().find(|(name, _, aliases)| {
+                *name == marker.kind || aliases.contains(&marker.kind.as_str())
+            });
+            match builtin {
+                Some((name, icon, _)) => (*name, icon.to_string(), None, None),
+                None => (
+                    marker.kind.as_str(),
+                    BUILTIN_CALLOUTS[0].1.to_string(),
+                    None,
+                    None,
+                ),
+            }
+        }
+    };
+    let title = title.unwrap_or_else(|| capitalize(&marker.kind));
+
+    let mut html = String::new();
+    let (container, title_tag) = match marker.fold {
+        Fold::None => ("div", "div"),
+        _ => ("details", "summary"),
+    };
+
+    html.push_str(&format!("<{container} class=\"callout\" data-callout=\""));
+    escape_html(&mut html, kind).unwrap();
+    html.push('"');
+    if let Some(color) = color {
+        html.push_str(" style=\"--callout-color: ");
+        escape_html(&mut html, &color).unwrap();
+        html.push('"');
+    }
+    if marker.fold == Fold::Open {
+        html.push_str(" open");
+    }
+    html.push_str(&format!(
+        ">\n<{title_tag} class=\"callout-title\"><span class=\"callout-icon\">{icon}</span><span class=\"callout-title-inner\">"
+    ));
+
+    let close_html = match marker.fold {
+        Fold::None => "</div>\n</div>\n",
+        _ => "</div>\n</details>\n",
+    };
+
+    (html, title, close_html)
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{parse_marker, CalloutMarker, CalloutType, Fold};
+    use crate::render::{render_markdown, RenderOptions};
+
+    #[test]
+    fn test_parse_marker() {
+        assert_eq!(
+            parse_marker("[!WARNING]- Be careful"),
+            Some(CalloutMarker {
+                kind: "warning".to_string(),
+                fold: Fold::Closed,
+                title: "Be careful".to_string(),
+            })
+        );
+        assert_eq!(parse_marker("[!]"), None);
+        assert_eq!(parse_marker("[link]"), None);
+    }
+
+    #[test]
+    fn test_render_callout() {
+        let html = render_markdown("> [!tip] Title\n> Body", &RenderOptions::default()).html;
+        assert_eq!(
+            html,
+            "<div class=\"callout\" data-callout=\"tip\">\n\
+            <div class=\"callout-title\"><span class=\"callout-icon\">✦</span>\
+            <span class=\"callout-title-inner\">Title</span></div>\n\
+            <div class=\"callout-content\">\n<p>Body</p>\n</div>\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_foldable_callout_default_title() {
+        let html = render_markdown("> [!faq]-\n> Body", &RenderOptions::default()).html;
+        assert!(html.starts_with(
+            "<details class=\"callout\" data-callout=\"question\">\n\
+            <summary class=\"callout-title\">"
+        ));
+        assert!(html.contains("<span class=\"callout-title-inner\">Faq</span></summary>"));
+        assert!(html.ends_with("</div>\n</details>\n"));
+    }
+
+    #[test]
+    fn test_render_custom_callout() {
+        let mut options = RenderOptions::default();
+        options.callouts.insert(
+            "recipe".to_string(),
+            CalloutType {
+                title: Some("Recipe".to_string()),
+                icon: Some("🍲".to_string()),
+                color: Some("rgb(255, 145, 0)".to_string()),
+            },
+        );
+        let html = render_markdown("> [!recipe]+\n> Soup", &options).html;
+        assert!(html.starts_with(
+            "<details class=\"callout\" data-callout=\"recipe\" \
+            style=\"--callout-color: rgb(255, 145, 0)\" open>"
+        ));
+        assert!(html.contains("<span class=\"callout-title-inner\">Recipe</span>"));
+    }
+
+    #[test]
+    fn test_render_plain_blockquote() {
+        let html = render_markdown("> [link] text", &RenderOptions::default()).html;
+        assert_eq!(html, "<blockquote>\n<p>[link] text</p>\n</blockquote>\n");
+    }
+}
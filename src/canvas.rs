@@ -0,0 +1,372 @@
+use pulldown_cmark::escape::escape_html;
+use serde::Deserialize;
+use std::path::Path;
+use thiserror::Error;
+
+use crate::{note::Note, wikilink::Wikilink};
+
+/// Space around the nodes of a rendered board, in pixels.
+const BOARD_PADDING: f64 = 40.0;
+
+/// An Obsidian canvas, stored in the JSON Canvas format.
+///
+/// See <https://jsoncanvas.org/spec/1.0/>.
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct Canvas {
+    #[serde(default)]
+    pub nodes: Vec<CanvasNode>,
+
+    #[serde(default)]
+    pub edges: Vec<CanvasEdge>,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+pub struct CanvasNode {
+    pub id: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub color: Option<String>,
+
+    #[serde(flatten)]
+    pub kind: CanvasNodeKind,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(tag = "type", rename_all = "lowercase")]
+pub enum CanvasNodeKind {
+    Text {
+        text: String,
+    },
+    File {
+        file: String,
+        subpath: Option<String>,
+    },
+    Link {
+        url: String,
+    },
+    Group {
+        label: Option<String>,
+    },
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CanvasEdge {
+    pub id: String,
+    pub from_node: String,
+    pub from_side: Option<CanvasSide>,
+    pub to_node: String,
+    pub to_side: Option<CanvasSide>,
+    pub to_end: Option<String>,
+    pub color: Option<String>,
+    pub label: Option<String>,
+}
+
+#[derive(Deserialize, Debug, PartialEq, Clone, Copy)]
+#[serde(rename_all = "lowercase")]
+pub enum CanvasSide {
+    Top,
+    Right,
+    Bottom,
+    Left,
+}
+
+impl Canvas {
+    pub fn parse(content: &str) -> Result<Canvas, CanvasError> {
+        Ok(serde_json::from_str(content)?)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: &P) -> Result<Canvas, CanvasError> {
+        let content = std::fs::read_to_string(path)?;
+        Canvas::parse(&content)
+    }
+
+    fn node(&self, id: &str) -> Option<&CanvasNode> {
+        self.nodes.iter().find(|node| node.id == id)
+    }
+
+    /// Links to other vault items, from file nodes and from wikilinks written
+    /// in text nodes.
+    pub fn links(&self) -> Vec<Wikilink> {
+        let mut links = Vec::new();
+
+        for node in self.nodes.iter() {
+            match &node.kind {
+                CanvasNodeKind::File { file, .. } => {
+                    links.push(Wikilink::new(file_target(file), None))
+                }
+                CanvasNodeKind::Text { text } => {
+                    if let Ok(note) = Note::parse("", text) {
+                        links.extend(note.links.into_iter().filter(|link| !link.embedded));
+                    }
+                }
+                _ => {}
+            }
+        }
+
+        links
+    }
+
+    /// Edges between two file nodes, as `(from, to)` link targets.
+    pub fn file_edges(&self) -> Vec<(String, String)> {
+        self.edges
+            .iter()
+            .filter_map(|edge| {
+                match (
+                    &self.node(&edge.from_node)?.kind,
+                    &self.node(&edge.to_node)?.kind,
+                ) {
+                    (
+                        CanvasNodeKind::File { file: from, .. },
+                        CanvasNodeKind::File { file: to, .. },
+                    ) => Some((file_target(from), file_target(to))),
+                    _ => None,
+                }
+            })
+            .collect()
+    }
+
+    /// Bounding box of all nodes as `(x, y, width, height)`.
+    fn bounds(&self) -> (f64, f64, f64, f64) {
+        if self.nodes.is_empty() {
+            return (0.0, 0.0, 0.0, 0.0);
+        }
+
+        let min_x = self.nodes.iter().map(|n| n.x).fold(f64::INFINITY, f64::min);
+        let min_y = self.nodes.iter().map(|n| n.y).fold(f64::INFINITY, f64::min);
+        let max_x = self
+            .nodes
+            .iter()
+            .map(|n| n.x + n.width)
+            .fold(f64::NEG_INFINITY, f64::max);
+        let max_y = self
+            .nodes
+            .iter()
+            .map(|n| n.y + n.height)
+            .fold(f64::NEG_INFINITY, f64::max);
+
+        (min_x, min_y, max_x - min_x, max_y - min_y)
+    }
+}
+
+/// Link target of a file node: notes are linked without their extension.
+pub(crate) fn file_target(file: &str) -> String {
+    file.strip_suffix(".md").unwrap_or(file).to_string()
+}
+
+/// Render a canvas as positioned cards, with its edges drawn in an SVG layer.
+///
+/// `node_html` returns the content of a card; groups are rendered behind the
+/// other nodes.
+pub(crate) fn render_board<F, E>(canvas: &Canvas, mut node_html: F) -> Result<String, E>
+where
+    F: FnMut(&CanvasNode) -> Result<String, E>,
+{
+    let (min_x, min_y, width, height) = canvas.bounds();
+    let offset_x = BOARD_PADDING - min_x;
+    let offset_y = BOARD_PADDING - min_y;
+    let width = width + 2.0 * BOARD_PADDING;
+    let height = height + 2.0 * BOARD_PADDING;
+
+    let mut html = format!(
+        "<div class=\"canvas\" style=\"width: {width}px; height: {height}px\">\n\
+        <svg class=\"canvas-edges\" width=\"{width}\" height=\"{height}\">\
+        <defs><marker id=\"canvas-arrow\" viewBox=\"0 0 10 10\" refX=\"10\" refY=\"5\" \
+        markerWidth=\"8\" markerHeight=\"8\" orient=\"auto-start-reverse\">\
+        <path d=\"M 0 0 L 10 5 L 0 10 z\"></path></marker></defs>\n"
+    );
+
+    for edge in canvas.edges.iter() {
+        let (from, to) = match (canvas.node(&edge.from_node), canvas.node(&edge.to_node)) {
+            (Some(from), Some(to)) => (from, to),
+            _ => continue,
+        };
+
+        let (x1, y1) = anchor(from, edge.from_side.unwrap_or(CanvasSide::Right));
+        let (x2, y2) = anchor(to, edge.to_side.unwrap_or(CanvasSide::Left));
+        let (x1, y1, x2, y2) = (x1 + offset_x, y1 + offset_y, x2 + offset_x, y2 + offset_y);
+
+        let (color, style) = color_attributes(edge.color.as_deref());
+        html.push_str(&format!(
+            "<line x1=\"{x1}\" y1=\"{y1}\" x2=\"{x2}\" y2=\"{y2}\"{color}{}{}></line>\n",
+            if style.is_empty() {
+                String::new()
+            } else {
+                format!(" style=\"{style}\"")
+            },
+            match edge.to_end.as_deref() {
+                Some("none") => "",
+                _ => " marker-end=\"url(#canvas-arrow)\"",
+            }
+        ));
+
+        if let Some(label) = &edge.label {
+            html.push_str(&format!(
+                "<text x=\"{}\" y=\"{}\" text-anchor=\"middle\">",
+                (x1 + x2) / 2.0,
+                (y1 + y2) / 2.0
+            ));
+            escape_html(&mut html, label).unwrap();
+            html.push_str("</text>\n");
+        }
+    }
+    html.push_str("</svg>\n");
+
+    let (groups, nodes): (Vec<&CanvasNode>, Vec<&CanvasNode>) = canvas
+        .nodes
+        .iter()
+        .partition(|node| matches!(node.kind, CanvasNodeKind::Group { .. }));
+
+    for node in groups.into_iter().chain(nodes) {
+        let kind = match node.kind {
+            CanvasNodeKind::Text { .. } => "text",
+            CanvasNodeKind::File { .. } => "file",
+            CanvasNodeKind::Link { .. } => "link",
+            CanvasNodeKind::Group { .. } => "group",
+        };
+
+        let (color, mut style) = color_attributes(node.color.as_deref());
+        if !style.is_empty() {
+            style.insert_str(0, "; ");
+        }
+        html.push_str(&format!(
+            "<div class=\"canvas-node canvas-{kind}\" \
+            style=\"left: {}px; top: {}px; width: {}px; height: {}px{style}\"{color}>\n",
+            node.x + offset_x,
+            node.y + offset_y,
+            node.width,
+            node.height,
+        ));
+        html.push_str(&node_html(node)?);
+        html.push_str("</div>\n");
+    }
+
+    html.push_str("</div>\n");
+    Ok(html)
+}
+
+/// Point where an edge meets a node.
+fn anchor(node: &CanvasNode, side: CanvasSide) -> (f64, f64) {
+    match side {
+        CanvasSide::Top => (node.x + node.width / 2.0, node.y),
+        CanvasSide::Right => (node.x + node.width, node.y + node.height / 2.0),
+        CanvasSide::Bottom => (node.x + node.width / 2.0, node.y + node.height),
+        CanvasSide::Left => (node.x, node.y + node.height / 2.0),
+    }
+}
+
+/// Canvas colors are either a preset number, exposed as `data-color`, or a
+/// hex color, exposed as the `--canvas-color` CSS variable. Returns the
+/// attribute and the style declaration.
+fn color_attributes(color: Option<&str>) -> (String, String) {
+    let mut escaped = String::new();
+    match color {
+        Some(color) if color.starts_with('#') => {
+            escape_html(&mut escaped, color).unwrap();
+            (String::new(), format!("--canvas-color: {escaped}"))
+        }
+        Some(color) => {
+            escape_html(&mut escaped, color).unwrap();
+            (format!(" data-color=\"{escaped}\""), String::new())
+        }
+        None => (String::new(), String::new()),
+    }
+}
+
+/// Group nodes' labels and link nodes' URLs, escaped.
+pub(crate) fn default_node_html(node: &CanvasNode) -> String {
+    let mut html = String::new();
+    match &node.kind {
+        CanvasNodeKind::Group { label: Some(label) } => {
+            html.push_str("<span class=\"canvas-group-label\">");
+            escape_html(&mut html, label).unwrap();
+            html.push_str("</span>\n");
+        }
+        CanvasNodeKind::Link { url } => {
+            html.push_str("<a href=\"");
+            escape_html(&mut html, url).unwrap();
+            html.push_str("\">");
+            escape_html(&mut html, url).unwrap();
+            html.push_str("</a>\n");
+        }
+        _ => {}
+    }
+    html
+}
+
+#[derive(Error, Debug)]
+pub enum CanvasError {
+    #[error("io error")]
+    IOError(#[from] std::io::Error),
+
+    #[error("invalid canvas JSON")]
+    JsonError(#[from] serde_json::Error),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_board, Canvas, CanvasNodeKind, CanvasSide};
+    use crate::wikilink::Wikilink;
+
+    const CANVAS: &str = r#"{
+        "nodes": [
+            {"id": "a", "type": "text", "text": "See [[Markdown]]", "x": -100, "y": 0, "width": 200, "height": 100},
+            {"id": "b", "type": "file", "file": "folder1/Page Name.md", "x": 200, "y": 0, "width": 200, "height": 100},
+            {"id": "c", "type": "file", "file": "WikiLink.md", "x": 200, "y": 200, "width": 200, "height": 100, "color": "2"},
+            {"id": "d", "type": "link", "url": "https://example.com", "x": 500, "y": 0, "width": 200, "height": 100},
+            {"id": "g", "type": "group", "label": "Group", "x": -120, "y": -20, "width": 840, "height": 340}
+        ],
+        "edges": [
+            {"id": "e1", "fromNode": "a", "fromSide": "right", "toNode": "b", "toSide": "left"},
+            {"id": "e2", "fromNode": "b", "fromSide": "bottom", "toNode": "c", "toSide": "top", "label": "next"}
+        ]
+    }"#;
+
+    #[test]
+    fn test_parse_canvas() {
+        let canvas = Canvas::parse(CANVAS).unwrap();
+        assert_eq!(canvas.nodes.len(), 5);
+        assert_eq!(
+            canvas.nodes[1].kind,
+            CanvasNodeKind::File {
+                file: "folder1/Page Name.md".to_string(),
+                subpath: None
+            }
+        );
+        assert_eq!(canvas.edges[1].from_side, Some(CanvasSide::Bottom));
+        assert_eq!(canvas.edges[1].label.as_deref(), Some("next"));
+    }
+
+    #[test]
+    fn test_canvas_links() {
+        let canvas = Canvas::parse(CANVAS).unwrap();
+        assert_eq!(
+            canvas.links(),
+            vec![
+                Wikilink::new("Markdown", None),
+                Wikilink::new("folder1/Page Name", None),
+                Wikilink::new("WikiLink", None),
+            ]
+        );
+        assert_eq!(
+            canvas.file_edges(),
+            vec![("folder1/Page Name".to_string(), "WikiLink".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_render_board_positions() {
+        let canvas = Canvas::parse(CANVAS).unwrap();
+        let html = render_board(&canvas, |_| Ok::<_, ()>(String::new())).unwrap();
+        assert!(html.starts_with("<div class=\"canvas\" style=\"width: 920px; height: 420px\">"));
+        assert!(html.contains(
+            "<div class=\"canvas-node canvas-text\" \
+            style=\"left: 60px; top: 60px; width: 200px; height: 100px\">"
+        ));
+        assert!(html.contains("<line x1=\"260\" y1=\"110\" x2=\"360\" y2=\"110\""));
+        // groups are drawn first
+        assert!(html.find("canvas-group").unwrap() < html.find("canvas-text").unwrap());
+    }
+}
@@ -0,0 +1,142 @@
+use serde::Deserialize;
+use std::collections::HashMap;
+
+use crate::{
+    attachment::FileType, callout::CalloutType, feed::FeedConfig, images::ImagesConfig,
+    inline_field::InlineFieldStyle, menu::MenuConfig, mermaid::MermaidConfig,
+    render::RenderOptions, schema::MetadataSchema,
+};
+
+/// Typed view over the options in `site.yaml` that change how the site is
+/// built. The raw file is still passed to templates as `site`.
+#[derive(Deserialize, Debug)]
+#[serde(default)]
+pub(crate) struct SiteConfig {
+    /// Build the built-in full-text search index.
+    pub search: bool,
+
+    /// Use pagefind instead of the built-in search index.
+    pub pagefind: bool,
+
+    /// Note rendered as the site's `index.html`, instead of a listing of
+    /// the vault's root folder.
+    pub home: Option<String>,
+
+    pub menu: MenuConfig,
+
+    /// Fields notes' metadata can have, checked when the vault is read.
+    pub schema: MetadataSchema,
+
+    /// Atom feed of the notes with dates, written to `feed.xml`.
+    pub feed: Option<FeedConfig>,
+
+    /// Custom callout types, by name.
+    pub callouts: HashMap<String, CalloutType>,
+
+    pub mermaid: MermaidConfig,
+
+    pub images: ImagesConfig,
+
+    /// How `key:: value` inline fields are written in pages.
+    pub inline_fields: InlineFieldStyle,
+
+    /// Attachment types, by file extension, added to the built-in ones.
+    pub file_types: HashMap<String, FileType>,
+
+    /// Copy every attachment of a known type in the vault, not only the ones
+    /// pages reference.
+    pub copy_all_attachments: bool,
+
+    /// Size, in megabytes, above which copied attachments are reported. `0`
+    /// disables the warning.
+    pub large_attachment_mb: u64,
+
+    /// Paths in the output directory written by other tools that stale
+    /// output removal and `clean` leave alone, besides pagefind's
+    /// `_pagefind`, which is always kept.
+    pub keep: Vec<String>,
+}
+
+impl Default for SiteConfig {
+    fn default() -> Self {
+        Self {
+            search: true,
+            pagefind: false,
+            home: None,
+            menu: MenuConfig::default(),
+            schema: MetadataSchema::default(),
+            feed: None,
+            callouts: HashMap::new(),
+            mermaid: MermaidConfig::default(),
+            images: ImagesConfig::default(),
+            inline_fields: InlineFieldStyle::default(),
+            file_types: HashMap::new(),
+            copy_all_attachments: false,
+            large_attachment_mb: 5,
+            keep: Vec::new(),
+        }
+    }
+}
+
+impl SiteConfig {
+    /// Options in the `site.yaml` at `path`, or the defaults when there is
+    /// no such file.
+    pub fn from_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, serde_yaml::Error> {
+        match std::fs::File::open(path) {
+            Ok(file) => serde_yaml::from_reader(file),
+            Err(_) => Ok(SiteConfig::default()),
+        }
+    }
+
+    pub fn render_options(&self) -> RenderOptions {
+        RenderOptions {
+            callouts: self.callouts.clone(),
+            mermaid_command: self.mermaid.command.clone(),
+            inline_fields: self.inline_fields,
+        }
+    }
+}
+
+impl TryFrom<&serde_yaml::Value> for SiteConfig {
+    type Error = serde_yaml::Error;
+
+    fn try_from(value: &serde_yaml::Value) -> Result<Self, Self::Error> {
+        serde_yaml::from_value(value.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::SiteConfig;
+    use crate::{images::OutputFormat, inline_field::InlineFieldStyle};
+
+    #[test]
+    fn test_site_config_defaults() {
+        let value: serde_yaml::Value = serde_yaml::from_str("title: Notes").unwrap();
+        let config = SiteConfig::try_from(&value).unwrap();
+        assert!(config.search);
+        assert!(!config.pagefind);
+        assert!(config.keep.is_empty());
+        assert_eq!(config.inline_fields, InlineFieldStyle::Keep);
+    }
+
+    #[test]
+    fn test_site_config_images() {
+        let value: serde_yaml::Value =
+            serde_yaml::from_str("images:\n  widths: [480, 960]\n  formats: [avif, webp]").unwrap();
+        let config = SiteConfig::try_from(&value).unwrap();
+        assert_eq!(config.images.widths, vec![480, 960]);
+        assert_eq!(
+            config.images.formats,
+            vec![OutputFormat::Avif, OutputFormat::Webp]
+        );
+        assert_eq!(config.images.quality, 80);
+    }
+
+    #[test]
+    fn test_site_config_disable_search() {
+        let value: serde_yaml::Value = serde_yaml::from_str("search: false").unwrap();
+        let config = SiteConfig::try_from(&value).unwrap();
+        assert!(!config.search);
+    }
+}
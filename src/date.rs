@@ -0,0 +1,558 @@
+use minijinja::{Environment, Error, ErrorKind, Value};
+use serde::{Serialize, Serializer};
+use std::{
+    fmt::{Display, Write},
+    str::FromStr,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const MONTHS: [&str; 12] = [
+    "January",
+    "February",
+    "March",
+    "April",
+    "May",
+    "June",
+    "July",
+    "August",
+    "September",
+    "October",
+    "November",
+    "December",
+];
+
+const WEEKDAYS: [&str; 7] = [
+    "Sunday",
+    "Monday",
+    "Tuesday",
+    "Wednesday",
+    "Thursday",
+    "Friday",
+    "Saturday",
+];
+
+/// A date, or a date and time, of a note's metadata, such as its `date`.
+///
+/// Written `YYYY-MM-DD`, as in YAML timestamps and Obsidian's properties,
+/// or with `/` or `.` between the parts, optionally followed by a time
+/// (`2024-03-01T10:30`, `2024-03-01 10:30:00`). Daily note links such as
+/// `[[2024-03-01]]` are dates too. Times can end with `Z` or a UTC offset
+/// such as `+02:00`; times without one are read as UTC.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Date {
+    pub year: i32,
+    pub month: u32,
+    pub day: u32,
+
+    /// Time of day of datetimes.
+    pub time: Option<Time>,
+
+    /// UTC offset of the time, in minutes, when it is written with one.
+    pub offset: Option<i32>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time {
+    pub hour: u32,
+    pub minute: u32,
+    pub second: u32,
+}
+
+impl Date {
+    /// Current date and time, in UTC.
+    pub fn now() -> Date {
+        let seconds = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map(|duration| duration.as_secs() as i64)
+            .unwrap_or(0);
+        Date::from_timestamp(seconds)
+    }
+
+    /// Date and time `seconds` after the Unix epoch.
+    pub fn from_timestamp(seconds: i64) -> Date {
+        let (year, month, day) = civil_from_days(seconds.div_euclid(86400));
+        let seconds = seconds.rem_euclid(86400) as u32;
+        Date {
+            year,
+            month,
+            day,
+            time: Some(Time {
+                hour: seconds / 3600,
+                minute: seconds / 60 % 60,
+                second: seconds % 60,
+            }),
+            offset: None,
+        }
+    }
+
+    /// Seconds since the Unix epoch, at midnight for dates without a time.
+    pub fn timestamp(&self) -> i64 {
+        let time = self.time.unwrap_or(Time {
+            hour: 0,
+            minute: 0,
+            second: 0,
+        });
+        days_from_civil(self.year, self.month, self.day) * 86400
+            + (time.hour * 3600 + time.minute * 60 + time.second) as i64
+            - self.offset.unwrap_or(0) as i64 * 60
+    }
+
+    /// Day of the week, from `0` for Sunday.
+    fn weekday(&self) -> usize {
+        (days_from_civil(self.year, self.month, self.day) + 4).rem_euclid(7) as usize
+    }
+
+    /// The date written with a `strftime`-like `format`: `%Y`, `%y`, `%m`,
+    /// `%d`, `%e`, `%B`, `%b`, `%A`, `%a`, `%j`, `%H`, `%I`, `%M`, `%S`, `%p`
+    /// and `%%`. `%-m`, `%-d`, `%-H` and `%-I` leave out the leading zero.
+    pub fn format(&self, format: &str) -> String {
+        let time = self.time.unwrap_or(Time {
+            hour: 0,
+            minute: 0,
+            second: 0,
+        });
+        let hour12 = match time.hour % 12 {
+            0 => 12,
+            hour => hour,
+        };
+
+        let mut output = String::with_capacity(format.len());
+        let mut chars = format.chars();
+        while let Some(c) = chars.next() {
+            if c != '%' {
+                output.push(c);
+                continue;
+            }
+
+            let mut spec = chars.next();
+            let padded = spec != Some('-');
+            if !padded {
+                spec = chars.next();
+            }
+            let number = |output: &mut String, value: u32| {
+                if padded {
+                    write!(output, "{value:02}").unwrap()
+                } else {
+                    write!(output, "{value}").unwrap()
+                }
+            };
+
+            match spec {
+                Some('Y') => write!(output, "{}", self.year).unwrap(),
+                Some('y') => write!(output, "{:02}", self.year.rem_euclid(100)).unwrap(),
+                Some('m') => number(&mut output, self.month),
+                Some('d') => number(&mut output, self.day),
+                Some('e') => write!(output, "{:>2}", self.day).unwrap(),
+                Some('B') => output.push_str(MONTHS[self.month as usize - 1]),
+                Some('b') => output.push_str(&MONTHS[self.month as usize - 1][..3]),
+                Some('A') => output.push_str(WEEKDAYS[self.weekday()]),
+                Some('a') => output.push_str(&WEEKDAYS[self.weekday()][..3]),
+                Some('j') => {
+                    let day = days_from_civil(self.year, self.month, self.day)
+                        - days_from_civil(self.year, 1, 1)
+                        + 1;
+                    write!(output, "{day:03}").unwrap()
+                }
+                Some('H') => number(&mut output, time.hour),
+                Some('I') => number(&mut output, hour12),
+                Some('M') => write!(output, "{:02}", time.minute).unwrap(),
+                Some('S') => write!(output, "{:02}", time.second).unwrap(),
+                Some('p') => output.push_str(if time.hour < 12 { "AM" } else { "PM" }),
+                Some('%') => output.push('%'),
+                // kept as written
+                Some(other) => {
+                    output.push('%');
+                    if !padded {
+                        output.push('-');
+                    }
+                    output.push(other);
+                }
+                None => output.push('%'),
+            }
+        }
+
+        output
+    }
+
+    /// How long before or after `now` the date is, such as `3 days ago` or
+    /// `in 2 months`. Dates without a time are compared by day.
+    pub fn relative_to(&self, now: &Date) -> String {
+        if self.time.is_none() {
+            let days = days_from_civil(self.year, self.month, self.day)
+                - days_from_civil(now.year, now.month, now.day);
+            return match days {
+                0 => "today".to_string(),
+                -1 => "yesterday".to_string(),
+                1 => "tomorrow".to_string(),
+                _ => relative_text(days * 86400),
+            };
+        }
+
+        match self.timestamp() - now.timestamp() {
+            seconds if seconds.abs() < 60 => "just now".to_string(),
+            seconds => relative_text(seconds),
+        }
+    }
+
+    /// The date and time in RFC 3339, in UTC, as in feeds.
+    pub fn rfc3339(&self) -> String {
+        Date::from_timestamp(self.timestamp()).format("%Y-%m-%dT%H:%M:%SZ")
+    }
+}
+
+/// Dates are in the order of the time they are at, whatever their offset.
+impl Ord for Date {
+    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
+        self.timestamp()
+            .cmp(&other.timestamp())
+            .then_with(|| (self.time, self.offset).cmp(&(other.time, other.offset)))
+    }
+}
+
+impl PartialOrd for Date {
+    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+/// Serialized as written by `Display`, for templates.
+impl Serialize for Date {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// `seconds` from now, in the largest unit that fits.
+fn relative_text(seconds: i64) -> String {
+    const UNITS: [(&str, i64); 6] = [
+        ("year", 365 * 86400),
+        ("month", 30 * 86400),
+        ("week", 7 * 86400),
+        ("day", 86400),
+        ("hour", 3600),
+        ("minute", 60),
+    ];
+
+    let (unit, size) = UNITS
+        .iter()
+        .find(|(_, size)| seconds.abs() >= *size)
+        .unwrap_or(&UNITS[UNITS.len() - 1]);
+    let count = (seconds.abs() / size).max(1);
+    let plural = if count == 1 { "" } else { "s" };
+
+    if seconds < 0 {
+        format!("{count} {unit}{plural} ago")
+    } else {
+        format!("in {count} {unit}{plural}")
+    }
+}
+
+impl FromStr for Date {
+    type Err = ();
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        let value = value.trim();
+        // daily note links
+        let value = value
+            .strip_prefix("[[")
+            .and_then(|value| value.strip_suffix("]]"))
+            .unwrap_or(value);
+
+        let date = value.get(..10).ok_or(())?;
+        let separator = date
+            .chars()
+            .nth(4)
+            .filter(|c| "-/.".contains(*c))
+            .ok_or(())?;
+        let mut parts = date.split(separator);
+        let (Some(year), Some(month), Some(day), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err(());
+        };
+        if year.len() != 4 || month.len() != 2 || day.len() != 2 {
+            return Err(());
+        }
+
+        let (time, offset) = parse_time(&value[10..])?;
+        let date = Date {
+            year: parse_digits(year)? as i32,
+            month: parse_digits(month)?,
+            day: parse_digits(day)?,
+            time,
+            offset,
+        };
+        if !(1..=12).contains(&date.month)
+            || !(1..=days_in_month(date.year, date.month)).contains(&date.day)
+        {
+            return Err(());
+        }
+
+        Ok(date)
+    }
+}
+
+/// The time after the date of a datetime, if any, without fractions of a
+/// second, and its UTC offset in minutes, if it has one.
+fn parse_time(value: &str) -> Result<(Option<Time>, Option<i32>), ()> {
+    if value.is_empty() {
+        return Ok((None, None));
+    }
+
+    let value = value.strip_prefix(['T', ' ']).ok_or(())?.trim_start();
+    let end = value
+        .find(|c: char| !(c.is_ascii_digit() || c == ':'))
+        .unwrap_or(value.len());
+    let zone = value[end..].trim_start();
+    // fractions of a second, then `Z` or an offset such as `+02:00`
+    let zone = zone
+        .strip_prefix('.')
+        .map(|rest| rest.trim_start_matches(|c: char| c.is_ascii_digit()))
+        .unwrap_or(zone);
+    let offset = match zone {
+        "" => None,
+        "Z" => Some(0),
+        zone => Some(parse_offset(zone)?),
+    };
+
+    let mut parts = value[..end].split(':');
+    let hour = parse_digits(parts.next().ok_or(())?)?;
+    let minute = parse_digits(parts.next().ok_or(())?)?;
+    let second = parts.next().map(parse_digits).transpose()?.unwrap_or(0);
+    if parts.next().is_some() || hour > 23 || minute > 59 || second > 60 {
+        return Err(());
+    }
+
+    let time = Time {
+        hour,
+        minute,
+        second,
+    };
+    Ok((Some(time), offset))
+}
+
+/// Minutes of a UTC offset written `+02:00`, `+0200` or `+02`.
+fn parse_offset(value: &str) -> Result<i32, ()> {
+    let sign = match value.chars().next() {
+        Some('+') => 1,
+        Some('-') => -1,
+        _ => return Err(()),
+    };
+    let digits = value[1..].replacen(':', "", 1);
+    let (hours, minutes) = match digits.len() {
+        2 => (parse_digits(&digits)?, 0),
+        4 => (parse_digits(&digits[..2])?, parse_digits(&digits[2..])?),
+        _ => return Err(()),
+    };
+    if hours > 23 || minutes > 59 {
+        return Err(());
+    }
+
+    Ok(sign * (hours * 60 + minutes) as i32)
+}
+
+fn parse_digits(value: &str) -> Result<u32, ()> {
+    if value.is_empty() || !value.chars().all(|c| c.is_ascii_digit()) {
+        return Err(());
+    }
+    value.parse().map_err(|_| ())
+}
+
+impl Display for Date {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:04}-{:02}-{:02}", self.year, self.month, self.day)?;
+        if let Some(time) = self.time {
+            write!(f, "T{:02}:{:02}:{:02}", time.hour, time.minute, time.second)?;
+        }
+        match self.offset {
+            Some(0) => write!(f, "Z")?,
+            Some(offset) => {
+                let sign = if offset < 0 { '-' } else { '+' };
+                let offset = offset.unsigned_abs();
+                write!(f, "{sign}{:02}:{:02}", offset / 60, offset % 60)?
+            }
+            None => {}
+        }
+        Ok(())
+    }
+}
+
+fn days_in_month(year: i32, month: u32) -> u32 {
+    let leap = year % 4 == 0 && (year % 100 != 0 || year % 400 == 0);
+    match month {
+        2 if leap => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Days since the Unix epoch of a date of the proleptic Gregorian calendar.
+fn days_from_civil(year: i32, month: u32, day: u32) -> i64 {
+    let year = year as i64 - if month <= 2 { 1 } else { 0 };
+    let era = year.div_euclid(400);
+    let year_of_era = year - era * 400;
+    let month = month as i64;
+    let day_of_year = (153 * (month + if month > 2 { -3 } else { 9 }) + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Date `days` after the Unix epoch.
+fn civil_from_days(days: i64) -> (i32, u32, u32) {
+    let days = days + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    } as u32;
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year as i32, month, day)
+}
+
+/// Add the `dateformat` and `relative_time` filters to `env`, for templates
+/// to write dates of the metadata: `{{ note.metadata.date | dateformat("%B
+/// %-d, %Y") }}` or `{{ note.metadata.updated | relative_time }}`. Relative
+/// times are from the time of the build.
+pub(crate) fn add_date_filters(env: &mut Environment) {
+    env.add_filter("dateformat", |value: Value, format: Option<String>| {
+        let date = template_date(&value)?;
+        Ok::<_, Error>(date.format(format.as_deref().unwrap_or("%Y-%m-%d")))
+    });
+
+    let now = Date::now();
+    env.add_filter("relative_time", move |value: Value| {
+        Ok::<_, Error>(template_date(&value)?.relative_to(&now))
+    });
+}
+
+fn template_date(value: &Value) -> Result<Date, Error> {
+    value
+        .as_str()
+        .and_then(|value| value.parse().ok())
+        .ok_or_else(|| {
+            Error::new(
+                ErrorKind::InvalidOperation,
+                format!("{value} is not a date"),
+            )
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{add_date_filters, Date, Time};
+    use minijinja::{context, Environment};
+
+    fn date(value: &str) -> Date {
+        value.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_date() {
+        assert_eq!(
+            date("2024-03-01"),
+            Date {
+                year: 2024,
+                month: 3,
+                day: 1,
+                time: None,
+                offset: None
+            }
+        );
+        assert_eq!(date("2024/03/01"), date("[[2024-03-01]]"));
+        assert_eq!(
+            date("2024-03-01 10:30").time,
+            Some(Time {
+                hour: 10,
+                minute: 30,
+                second: 0
+            })
+        );
+        assert_eq!(
+            date("2024-03-01T10:30:15.250+02:00").to_string(),
+            "2024-03-01T10:30:15+02:00"
+        );
+        assert_eq!(
+            date("2024-03-01T10:30:15+0000").to_string(),
+            "2024-03-01T10:30:15Z"
+        );
+        assert_eq!(date("2024-03-01T10:30-05").offset, Some(-300));
+
+        for invalid in [
+            "2024-02-30",
+            "2024-13-01",
+            "2024-3-1",
+            "2024-03-01 text",
+            "2024-03-01T25:00",
+            "2024-03-01T10:00+2",
+            "2024-03-01T10:00 UTC",
+            "Example",
+            "2024",
+        ] {
+            assert!(invalid.parse::<Date>().is_err(), "{invalid}");
+        }
+    }
+
+    #[test]
+    fn test_date_timestamp() {
+        let value = date("2024-02-29T23:59:59");
+        assert_eq!(value.timestamp(), 1709251199);
+        assert_eq!(Date::from_timestamp(value.timestamp()), value);
+        assert_eq!(date("1969-12-31").timestamp(), -86400);
+        assert_eq!(
+            date("2024-03-01T12:00+02:00").timestamp(),
+            date("2024-03-01T10:00Z").timestamp()
+        );
+        assert!(date("2024-03-01T12:00+02:00") < date("2024-03-01T11:00Z"));
+    }
+
+    #[test]
+    fn test_format_date() {
+        let value = date("2024-03-01T09:05:00");
+        assert_eq!(
+            value.format("%A, %B %-d, %Y %-I:%M %p"),
+            "Friday, March 1, 2024 9:05 AM"
+        );
+        assert_eq!(
+            value.format("%a %d %b %y %H:%M:%S %j %% %q"),
+            "Fri 01 Mar 24 09:05:00 061 % %q"
+        );
+        assert_eq!(value.rfc3339(), "2024-03-01T09:05:00Z");
+        assert_eq!(
+            date("2024-03-01T01:30+02:00").rfc3339(),
+            "2024-02-29T23:30:00Z"
+        );
+    }
+
+    #[test]
+    fn test_relative_date() {
+        let now = date("2024-03-10T12:00:00");
+        assert_eq!(date("2024-03-10").relative_to(&now), "today");
+        assert_eq!(date("2024-03-09").relative_to(&now), "yesterday");
+        assert_eq!(date("2024-03-07").relative_to(&now), "3 days ago");
+        assert_eq!(date("2024-03-24").relative_to(&now), "in 2 weeks");
+        assert_eq!(date("2023-01-01").relative_to(&now), "1 year ago");
+        assert_eq!(date("2024-03-10T11:59:30").relative_to(&now), "just now");
+        assert_eq!(date("2024-03-10T15:00:00").relative_to(&now), "in 3 hours");
+    }
+
+    #[test]
+    fn test_date_filters() {
+        let mut env = Environment::new();
+        add_date_filters(&mut env);
+        let render = |template: &str| env.render_str(template, context! { date => "2024-03-01" });
+        assert_eq!(
+            render("{{ date | dateformat('%b %-d, %Y') }}").unwrap(),
+            "Mar 1, 2024"
+        );
+        assert_eq!(render("{{ date | dateformat }}").unwrap(), "2024-03-01");
+        assert!(render("{{ 'soon' | relative_time }}").is_err());
+    }
+}
@@ -0,0 +1,603 @@
+use pulldown_cmark::escape::escape_html;
+use serde::Deserialize;
+use std::{collections::HashMap, path::Path};
+use thiserror::Error;
+
+use crate::{note::Note, wikilink::Wikilink};
+
+/// Space around the elements of a rendered drawing.
+const DRAWING_PADDING: f64 = 16.0;
+const LINE_HEIGHT: f64 = 1.25;
+const ARROWHEAD_LENGTH: f64 = 15.0;
+
+/// An Excalidraw drawing, from a `.excalidraw` file or from the `Drawing`
+/// section of an Obsidian Excalidraw plugin `.excalidraw.md` file.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Drawing {
+    pub elements: Vec<Element>,
+    pub app_state: AppState,
+    pub files: HashMap<String, DrawingFile>,
+}
+
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct AppState {
+    pub view_background_color: Option<String>,
+}
+
+/// An image embedded in a drawing.
+#[derive(Deserialize, Debug, Default, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct DrawingFile {
+    #[serde(rename = "dataURL")]
+    pub data_url: String,
+}
+
+#[derive(Deserialize, Debug, PartialEq)]
+#[serde(rename_all = "camelCase", default)]
+pub struct Element {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub x: f64,
+    pub y: f64,
+    pub width: f64,
+    pub height: f64,
+    pub angle: f64,
+    pub stroke_color: String,
+    pub background_color: String,
+    pub stroke_width: f64,
+    pub stroke_style: String,
+    pub opacity: f64,
+    pub is_deleted: bool,
+    pub roundness: Option<serde_json::Value>,
+    pub points: Vec<(f64, f64)>,
+    pub start_arrowhead: Option<String>,
+    pub end_arrowhead: Option<String>,
+    pub text: String,
+    pub raw_text: Option<String>,
+    pub original_text: Option<String>,
+    pub font_size: f64,
+    pub font_family: u32,
+    pub text_align: String,
+    pub file_id: Option<String>,
+    pub link: Option<String>,
+}
+
+impl Default for Element {
+    fn default() -> Self {
+        Self {
+            kind: String::new(),
+            x: 0.0,
+            y: 0.0,
+            width: 0.0,
+            height: 0.0,
+            angle: 0.0,
+            stroke_color: "#1e1e1e".to_string(),
+            background_color: "transparent".to_string(),
+            stroke_width: 1.0,
+            stroke_style: "solid".to_string(),
+            opacity: 100.0,
+            is_deleted: false,
+            roundness: None,
+            points: Vec::new(),
+            start_arrowhead: None,
+            end_arrowhead: None,
+            text: String::new(),
+            raw_text: None,
+            original_text: None,
+            font_size: 20.0,
+            font_family: 1,
+            text_align: "left".to_string(),
+            file_id: None,
+            link: None,
+        }
+    }
+}
+
+impl Drawing {
+    /// Parse a drawing saved as Excalidraw JSON.
+    pub fn parse_json(content: &str) -> Result<Drawing, DrawingError> {
+        Ok(serde_json::from_str(content)?)
+    }
+
+    /// Parse an Obsidian Excalidraw plugin markdown file, reading the scene
+    /// from the `json` or `compressed-json` block in its `Drawing` section.
+    pub fn parse_markdown(content: &str) -> Result<Drawing, DrawingError> {
+        let section = content
+            .find("# Drawing")
+            .map(|pos| &content[pos..])
+            .ok_or(DrawingError::MissingScene)?;
+
+        for (fence, compressed) in [("```compressed-json", true), ("```json", false)] {
+            if let Some(start) = section.find(fence) {
+                let block = &section[start + fence.len()..];
+                let end = block.find("```").ok_or(DrawingError::MissingScene)?;
+                let block = &block[..end];
+
+                return if compressed {
+                    let block: String = block.chars().filter(|c| !c.is_whitespace()).collect();
+                    let json = decompress_from_base64(&block).ok_or(DrawingError::InvalidScene)?;
+                    Drawing::parse_json(&json)
+                } else {
+                    Drawing::parse_json(block)
+                };
+            }
+        }
+
+        Err(DrawingError::MissingScene)
+    }
+
+    pub fn from_file<P: AsRef<Path>>(path: &P) -> Result<Drawing, DrawingError> {
+        let content = std::fs::read_to_string(path)?;
+        if path.as_ref().to_string_lossy().ends_with(".md") {
+            Drawing::parse_markdown(&content)
+        } else {
+            Drawing::parse_json(&content)
+        }
+    }
+
+    fn visible_elements(&self) -> impl Iterator<Item = &Element> {
+        self.elements.iter().filter(|element| !element.is_deleted)
+    }
+
+    /// Wikilinks written in text elements or set as element links.
+    pub fn links(&self) -> Vec<Wikilink> {
+        let mut links: Vec<Wikilink> = Vec::new();
+
+        for element in self.visible_elements() {
+            let text = element
+                .raw_text
+                .as_ref()
+                .or(element.original_text.as_ref())
+                .unwrap_or(&element.text);
+
+            for text in [Some(text), element.link.as_ref()].into_iter().flatten() {
+                if let Ok(note) = Note::parse("", text) {
+                    for link in note.links {
+                        if !links.contains(&link) {
+                            links.push(link);
+                        }
+                    }
+                }
+            }
+        }
+
+        links
+    }
+
+    /// Bounding box of all visible elements as `(x, y, width, height)`.
+    fn bounds(&self) -> (f64, f64, f64, f64) {
+        let mut min = (f64::INFINITY, f64::INFINITY);
+        let mut max = (f64::NEG_INFINITY, f64::NEG_INFINITY);
+
+        for element in self.visible_elements() {
+            let corners = if element.points.is_empty() {
+                vec![(0.0, 0.0), (element.width, element.height)]
+            } else {
+                element.points.clone()
+            };
+            for (x, y) in corners {
+                min = (min.0.min(element.x + x), min.1.min(element.y + y));
+                max = (max.0.max(element.x + x), max.1.max(element.y + y));
+            }
+        }
+
+        if min.0 > max.0 {
+            return (0.0, 0.0, 0.0, 0.0);
+        }
+
+        (min.0, min.1, max.0 - min.0, max.1 - min.1)
+    }
+
+    /// Render the drawing as an SVG image.
+    ///
+    /// Shapes are drawn with straight strokes rather than Excalidraw's
+    /// hand-drawn style.
+    pub fn to_svg(&self) -> String {
+        let (x, y, width, height) = self.bounds();
+        let (x, y) = (x - DRAWING_PADDING, y - DRAWING_PADDING);
+        let (width, height) = (
+            width + 2.0 * DRAWING_PADDING,
+            height + 2.0 * DRAWING_PADDING,
+        );
+
+        let mut svg = format!(
+            "<svg class=\"excalidraw\" xmlns=\"http://www.w3.org/2000/svg\" \
+            viewBox=\"{x} {y} {width} {height}\" width=\"{width}\" height=\"{height}\">\n"
+        );
+
+        if let Some(background) = &self.app_state.view_background_color {
+            svg.push_str(&format!(
+                "<rect x=\"{x}\" y=\"{y}\" width=\"{width}\" height=\"{height}\" fill=\"{}\"></rect>\n",
+                escape(background)
+            ));
+        }
+
+        for element in self.visible_elements() {
+            svg.push_str(&self.element_svg(element));
+        }
+
+        svg.push_str("</svg>");
+        svg
+    }
+
+    fn element_svg(&self, element: &Element) -> String {
+        let mut attrs = format!(
+            " stroke=\"{}\" stroke-width=\"{}\"",
+            escape(&element.stroke_color),
+            element.stroke_width
+        );
+        match element.stroke_style.as_str() {
+            "dashed" => attrs.push_str(" stroke-dasharray=\"8 8\""),
+            "dotted" => attrs.push_str(" stroke-dasharray=\"1.5 6\""),
+            _ => {}
+        }
+        if element.opacity < 100.0 {
+            attrs.push_str(&format!(" opacity=\"{}\"", element.opacity / 100.0));
+        }
+        if element.angle != 0.0 {
+            attrs.push_str(&format!(
+                " transform=\"rotate({} {} {})\"",
+                element.angle.to_degrees(),
+                element.x + element.width / 2.0,
+                element.y + element.height / 2.0
+            ));
+        }
+        let fill = match element.background_color.as_str() {
+            "transparent" | "" => "none".to_string(),
+            color => escape(color),
+        };
+
+        let (x, y, w, h) = (element.x, element.y, element.width, element.height);
+        match element.kind.as_str() {
+            "rectangle" => {
+                let radius = if element.roundness.is_some() {
+                    (w.min(h) * 0.25).min(32.0)
+                } else {
+                    0.0
+                };
+                format!(
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"{w}\" height=\"{h}\" rx=\"{radius}\" fill=\"{fill}\"{attrs}></rect>\n"
+                )
+            }
+            "ellipse" => format!(
+                "<ellipse cx=\"{}\" cy=\"{}\" rx=\"{}\" ry=\"{}\" fill=\"{fill}\"{attrs}></ellipse>\n",
+                x + w / 2.0,
+                y + h / 2.0,
+                w / 2.0,
+                h / 2.0
+            ),
+            "diamond" => format!(
+                "<polygon points=\"{},{} {},{} {},{} {},{}\" fill=\"{fill}\"{attrs}></polygon>\n",
+                x + w / 2.0,
+                y,
+                x + w,
+                y + h / 2.0,
+                x + w / 2.0,
+                y + h,
+                x,
+                y + h / 2.0
+            ),
+            "line" | "arrow" | "freedraw" => {
+                let points: Vec<(f64, f64)> =
+                    element.points.iter().map(|(px, py)| (x + px, y + py)).collect();
+                let fill = if element.kind == "line" { fill } else { "none".to_string() };
+                let mut svg = format!(
+                    "<g{attrs} fill=\"none\" stroke-linecap=\"round\" stroke-linejoin=\"round\">\
+                    <polyline points=\"{}\" fill=\"{fill}\"></polyline>",
+                    points_attribute(&points)
+                );
+                if element.kind == "arrow" {
+                    let end = element.end_arrowhead.as_deref().unwrap_or("arrow");
+                    if let Some(head) = arrowhead(&points, end) {
+                        svg.push_str(&head);
+                    }
+                    if let Some(start) = element.start_arrowhead.as_deref() {
+                        let reversed: Vec<(f64, f64)> = points.iter().rev().cloned().collect();
+                        if let Some(head) = arrowhead(&reversed, start) {
+                            svg.push_str(&head);
+                        }
+                    }
+                }
+                svg.push_str("</g>\n");
+                svg
+            }
+            "text" => {
+                let (anchor, text_x) = match element.text_align.as_str() {
+                    "center" => ("middle", x + w / 2.0),
+                    "right" => ("end", x + w),
+                    _ => ("start", x),
+                };
+                let font_family = match element.font_family {
+                    2 => "Helvetica, Arial, sans-serif",
+                    3 => "Cascadia, Consolas, monospace",
+                    _ => "Virgil, 'Segoe Print', 'Comic Sans MS', cursive",
+                };
+                let mut svg = format!(
+                    "<text x=\"{text_x}\" y=\"{y}\" font-family=\"{font_family}\" font-size=\"{}\" \
+                    fill=\"{}\" text-anchor=\"{anchor}\" dominant-baseline=\"text-before-edge\"{}>",
+                    element.font_size,
+                    escape(&element.stroke_color),
+                    if element.opacity < 100.0 {
+                        format!(" opacity=\"{}\"", element.opacity / 100.0)
+                    } else {
+                        String::new()
+                    }
+                );
+                for (i, line) in element.text.lines().enumerate() {
+                    let dy = if i == 0 {
+                        0.0
+                    } else {
+                        element.font_size * LINE_HEIGHT
+                    };
+                    svg.push_str(&format!(
+                        "<tspan x=\"{text_x}\" dy=\"{dy}\">{}</tspan>",
+                        escape(line)
+                    ));
+                }
+                svg.push_str("</text>\n");
+                svg
+            }
+            "image" => match element.file_id.as_ref().and_then(|id| self.files.get(id)) {
+                Some(file) => format!(
+                    "<image href=\"{}\" x=\"{x}\" y=\"{y}\" width=\"{w}\" height=\"{h}\"></image>\n",
+                    escape(&file.data_url)
+                ),
+                None => format!(
+                    "<rect x=\"{x}\" y=\"{y}\" width=\"{w}\" height=\"{h}\" fill=\"none\"{attrs}></rect>\n"
+                ),
+            },
+            _ => String::new(),
+        }
+    }
+}
+
+fn points_attribute(points: &[(f64, f64)]) -> String {
+    points
+        .iter()
+        .map(|(x, y)| format!("{x},{y}"))
+        .collect::<Vec<String>>()
+        .join(" ")
+}
+
+/// Arrowhead at the last point of `points`, pointing along the last segment.
+fn arrowhead(points: &[(f64, f64)], kind: &str) -> Option<String> {
+    if kind == "none" || points.len() < 2 {
+        return None;
+    }
+
+    let (x1, y1) = points[points.len() - 2];
+    let (x2, y2) = points[points.len() - 1];
+    let angle = (y2 - y1).atan2(x2 - x1);
+    let spread = std::f64::consts::PI / 8.0;
+
+    let wing = |side: f64| {
+        (
+            x2 - ARROWHEAD_LENGTH * (angle + side * spread).cos(),
+            y2 - ARROWHEAD_LENGTH * (angle + side * spread).sin(),
+        )
+    };
+
+    Some(format!(
+        "<polyline points=\"{}\"></polyline>",
+        points_attribute(&[wing(1.0), (x2, y2), wing(-1.0)])
+    ))
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    escape_html(&mut escaped, text).unwrap();
+    escaped
+}
+
+/// Port of lz-string's `decompressFromBase64`, used by the Excalidraw plugin
+/// to store scenes as `compressed-json`.
+fn decompress_from_base64(input: &str) -> Option<String> {
+    const KEY: &[u8] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/=";
+
+    let values = input
+        .bytes()
+        .map(|byte| KEY.iter().position(|key| *key == byte).map(|v| v as u32))
+        .collect::<Option<Vec<u32>>>()?;
+
+    decompress(&values, 32)
+}
+
+fn decompress(values: &[u32], reset_value: u32) -> Option<String> {
+    struct Bits<'a> {
+        values: &'a [u32],
+        val: u32,
+        position: u32,
+        index: usize,
+        reset_value: u32,
+    }
+
+    impl Bits<'_> {
+        fn read(&mut self, count: u32) -> u32 {
+            let mut bits = 0;
+            for power in 0..count {
+                let resb = self.val & self.position;
+                self.position >>= 1;
+                if self.position == 0 {
+                    self.position = self.reset_value;
+                    self.val = self.values.get(self.index).copied().unwrap_or(0);
+                    self.index += 1;
+                }
+                if resb > 0 {
+                    bits |= 1 << power;
+                }
+            }
+            bits
+        }
+    }
+
+    let mut bits = Bits {
+        values,
+        val: *values.first()?,
+        position: reset_value,
+        index: 1,
+        reset_value,
+    };
+
+    // entries 0, 1 and 2 are reserved for the control codes
+    let mut dictionary: Vec<Vec<u16>> = vec![Vec::new(), Vec::new(), Vec::new()];
+    let mut enlarge_in: u32 = 4;
+    let mut num_bits: u32 = 3;
+
+    let first = match bits.read(2) {
+        0 => bits.read(8) as u16,
+        1 => bits.read(16) as u16,
+        _ => return Some(String::new()),
+    };
+    dictionary.push(vec![first]);
+    let mut w = vec![first];
+    let mut result = vec![first];
+
+    loop {
+        if bits.index > values.len() {
+            return None;
+        }
+
+        let mut code = bits.read(num_bits) as usize;
+        match code {
+            0 | 1 => {
+                let char_bits = if code == 0 { 8 } else { 16 };
+                dictionary.push(vec![bits.read(char_bits) as u16]);
+                code = dictionary.len() - 1;
+                enlarge_in -= 1;
+            }
+            2 => return Some(String::from_utf16_lossy(&result)),
+            _ => {}
+        }
+
+        if enlarge_in == 0 {
+            enlarge_in = 1 << num_bits;
+            num_bits += 1;
+        }
+
+        let entry = if code < dictionary.len() {
+            dictionary[code].clone()
+        } else if code == dictionary.len() {
+            let mut entry = w.clone();
+            entry.push(w[0]);
+            entry
+        } else {
+            return None;
+        };
+
+        result.extend_from_slice(&entry);
+
+        let mut new_entry = w;
+        new_entry.push(entry[0]);
+        dictionary.push(new_entry);
+        enlarge_in -= 1;
+
+        w = entry;
+
+        if enlarge_in == 0 {
+            enlarge_in = 1 << num_bits;
+            num_bits += 1;
+        }
+    }
+}
+
+#[derive(Error, Debug)]
+pub enum DrawingError {
+    #[error("io error")]
+    IOError(#[from] std::io::Error),
+
+    #[error("invalid drawing JSON")]
+    JsonError(#[from] serde_json::Error),
+
+    #[error("no drawing section found")]
+    MissingScene,
+
+    #[error("invalid compressed drawing")]
+    InvalidScene,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{decompress_from_base64, Drawing};
+    use crate::wikilink::Wikilink;
+
+    const SCENE: &str = r##"{
+        "type": "excalidraw",
+        "elements": [
+            {"type": "rectangle", "x": 0, "y": 0, "width": 100, "height": 50, "strokeColor": "#1e1e1e", "backgroundColor": "#ffc9c9"},
+            {"type": "text", "x": 10, "y": 10, "width": 80, "height": 25, "text": "See Note", "rawText": "See [[Note]]", "fontSize": 20},
+            {"type": "arrow", "x": 100, "y": 25, "width": 100, "height": 0, "points": [[0, 0], [100, 0]], "link": "[[Other|label]]"},
+            {"type": "ellipse", "x": 500, "y": 500, "width": 10, "height": 10, "isDeleted": true}
+        ],
+        "appState": {"viewBackgroundColor": "#ffffff"},
+        "files": {}
+    }"##;
+
+    #[test]
+    fn test_parse_json() {
+        let drawing = Drawing::parse_json(SCENE).unwrap();
+        assert_eq!(drawing.elements.len(), 4);
+        assert_eq!(drawing.elements[2].points, vec![(0.0, 0.0), (100.0, 0.0)]);
+        assert_eq!(
+            drawing.app_state.view_background_color.as_deref(),
+            Some("#ffffff")
+        );
+    }
+
+    #[test]
+    fn test_parse_markdown() {
+        let content = format!(
+            "---\nexcalidraw-plugin: parsed\n---\n\n# Text Elements\nSee [[Note]] ^abc\n\n%%\n# Drawing\n```json\n{SCENE}\n```\n%%"
+        );
+        let drawing = Drawing::parse_markdown(&content).unwrap();
+        assert_eq!(drawing.elements.len(), 4);
+    }
+
+    #[test]
+    fn test_parse_markdown_compressed() {
+        // LZString.compressToBase64('{"elements":[{"type":"text","text":"hi"}]}')
+        let content = "## Drawing\n```compressed-json\n\
+            N4IgpgNmC2YHYBcDOIBcBtUCCeAHMaICYA\nHgiADRGnmogAWAliAL4C6LQA==\n```\n";
+        let drawing = Drawing::parse_markdown(content).unwrap();
+        assert_eq!(drawing.elements[0].text, "hi");
+    }
+
+    #[test]
+    fn test_decompress_from_base64() {
+        // LZString.compressToBase64("hello hello hello")
+        assert_eq!(
+            decompress_from_base64("BYUwNmD2AEoTcpA=").as_deref(),
+            Some("hello hello hello")
+        );
+        // LZString.compressToBase64("ação 😀 unicode")
+        assert_eq!(
+            decompress_from_base64("IYc4xw9gBIvBuAB7UCuA7AlgYwgEwKZA").as_deref(),
+            Some("ação 😀 unicode")
+        );
+    }
+
+    #[test]
+    fn test_drawing_links() {
+        let drawing = Drawing::parse_json(SCENE).unwrap();
+        assert_eq!(
+            drawing.links(),
+            vec![
+                Wikilink::new("Note", None),
+                Wikilink::new("Other", Some("label")),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_drawing_svg() {
+        let svg = Drawing::parse_json(SCENE).unwrap().to_svg();
+        assert!(svg.starts_with("<svg class=\"excalidraw\" xmlns=\"http://www.w3.org/2000/svg\" viewBox=\"-16 -16 232 82\""));
+        assert!(svg.contains(
+            "<rect x=\"0\" y=\"0\" width=\"100\" height=\"50\" rx=\"0\" fill=\"#ffc9c9\""
+        ));
+        assert!(svg.contains("<tspan x=\"10\" dy=\"0\">See Note</tspan>"));
+        assert!(svg.contains("<polyline points=\"100,25 200,25\""));
+        assert!(!svg.contains("ellipse"));
+    }
+}
@@ -0,0 +1,161 @@
+use pulldown_cmark::escape::escape_html;
+use serde::Deserialize;
+use std::fmt::Write;
+
+use crate::date::Date;
+
+/// Name of the feed file in the output directory.
+pub(crate) const FEED_FILE: &str = "feed.xml";
+
+/// Atom feed options from `site.yaml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct FeedConfig {
+    /// URL the site is published at, such as `https://example.com`. Feed
+    /// readers need absolute links.
+    pub url: String,
+
+    /// Number of notes in the feed, the most recent ones.
+    #[serde(default = "default_limit")]
+    pub limit: usize,
+
+    /// Author of the notes. Atom feeds need one, so the site's title is
+    /// used when it is not set.
+    pub author: Option<FeedAuthor>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct FeedAuthor {
+    pub name: String,
+    pub email: Option<String>,
+    pub uri: Option<String>,
+}
+
+fn default_limit() -> usize {
+    20
+}
+
+/// A note in the feed.
+#[derive(Debug, PartialEq)]
+pub(crate) struct FeedEntry {
+    pub title: String,
+
+    /// Link to the page, absolute.
+    pub url: String,
+    pub published: Date,
+    pub updated: Date,
+    pub tags: Vec<String>,
+}
+
+/// Atom feed of `entries`, the most recently updated first, up to `limit`
+/// of them.
+pub(crate) fn atom_feed(
+    title: &str,
+    url: &str,
+    author: &FeedAuthor,
+    mut entries: Vec<FeedEntry>,
+    limit: usize,
+) -> String {
+    entries.sort_by(|a, b| {
+        b.updated
+            .cmp(&a.updated)
+            .then_with(|| a.title.cmp(&b.title))
+    });
+    entries.truncate(limit);
+
+    let updated = entries
+        .first()
+        .map(|entry| entry.updated)
+        .unwrap_or_else(Date::now);
+
+    let mut xml = String::new();
+    xml.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+    xml.push_str("<feed xmlns=\"http://www.w3.org/2005/Atom\">\n");
+    writeln!(xml, "  <title>{}</title>", escape(title)).unwrap();
+    writeln!(xml, "  <id>{}</id>", escape(url)).unwrap();
+    writeln!(xml, "  <link href=\"{}\"/>", escape(url)).unwrap();
+    writeln!(xml, "  <updated>{}</updated>", updated.rfc3339()).unwrap();
+    xml.push_str("  <author>\n");
+    writeln!(xml, "    <name>{}</name>", escape(&author.name)).unwrap();
+    if let Some(email) = &author.email {
+        writeln!(xml, "    <email>{}</email>", escape(email)).unwrap();
+    }
+    if let Some(uri) = &author.uri {
+        writeln!(xml, "    <uri>{}</uri>", escape(uri)).unwrap();
+    }
+    xml.push_str("  </author>\n");
+
+    for entry in entries {
+        xml.push_str("  <entry>\n");
+        writeln!(xml, "    <title>{}</title>", escape(&entry.title)).unwrap();
+        writeln!(xml, "    <id>{}</id>", escape(&entry.url)).unwrap();
+        writeln!(xml, "    <link href=\"{}\"/>", escape(&entry.url)).unwrap();
+        writeln!(
+            xml,
+            "    <published>{}</published>",
+            entry.published.rfc3339()
+        )
+        .unwrap();
+        writeln!(xml, "    <updated>{}</updated>", entry.updated.rfc3339()).unwrap();
+        for tag in entry.tags {
+            writeln!(xml, "    <category term=\"{}\"/>", escape(&tag)).unwrap();
+        }
+        xml.push_str("  </entry>\n");
+    }
+
+    xml.push_str("</feed>\n");
+    xml
+}
+
+fn escape(text: &str) -> String {
+    let mut escaped = String::new();
+    escape_html(&mut escaped, text).unwrap();
+    escaped
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{atom_feed, FeedAuthor, FeedEntry};
+
+    fn entry(title: &str, published: &str, updated: &str) -> FeedEntry {
+        FeedEntry {
+            title: title.to_string(),
+            url: format!("https://example.com/{title}.html"),
+            published: published.parse().unwrap(),
+            updated: updated.parse().unwrap(),
+            tags: vec!["rust".to_string()],
+        }
+    }
+
+    #[test]
+    fn test_atom_feed() {
+        let author = FeedAuthor {
+            name: "Ana".to_string(),
+            email: None,
+            uri: Some("https://example.com/about".to_string()),
+        };
+        let feed = atom_feed(
+            "Notes & more",
+            "https://example.com/",
+            &author,
+            vec![
+                entry("Old", "2023-01-01", "2023-01-01"),
+                entry("Edited", "2023-06-01", "2024-03-02T12:30+02:00"),
+                entry("New", "2024-03-01", "2024-03-01"),
+            ],
+            2,
+        );
+
+        assert!(feed.contains("<title>Notes &amp; more</title>"));
+        assert!(feed.contains(
+            "  <author>\n    <name>Ana</name>\n    <uri>https://example.com/about</uri>\n  </author>"
+        ));
+        assert!(feed.contains("<updated>2024-03-02T10:30:00Z</updated>\n  <author>"));
+        let titles: Vec<&str> = feed
+            .match_indices("<title>")
+            .map(|(start, _)| &feed[start + 7..start + feed[start..].find("</").unwrap()])
+            .collect();
+        assert_eq!(titles, vec!["Notes &amp; more", "Edited", "New"]);
+        assert!(feed.contains("<published>2023-06-01T00:00:00Z</published>"));
+        assert!(feed.contains("<category term=\"rust\"/>"));
+    }
+}
@@ -0,0 +1,445 @@
+use image::{
+    codecs::{avif::AvifEncoder, jpeg::JpegEncoder, png::PngEncoder, webp::WebPEncoder},
+    imageops::FilterType,
+    metadata::Orientation,
+    DynamicImage, ImageDecoder, ImageFormat, ImageReader,
+};
+use serde::Deserialize;
+use std::{
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+use thiserror::Error;
+
+/// AVIF encoder speed, from 1 (slowest, smallest) to 10 (fastest).
+const AVIF_SPEED: u8 = 6;
+
+/// Image processing options from `site.yaml`.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(default)]
+pub struct ImagesConfig {
+    /// Widths, in pixels, of the resized copies of embedded images. Widths
+    /// larger than an image are skipped.
+    pub widths: Vec<u32>,
+
+    /// Formats embedded images are transcoded to, besides their own, in the
+    /// order browsers should prefer them.
+    pub formats: Vec<OutputFormat>,
+
+    /// Quality of lossy encodings, AVIF and JPEG, from 1 to 100. WebP is
+    /// written lossless.
+    pub quality: u8,
+
+    /// `sizes` attribute of the responsive images.
+    pub sizes: String,
+}
+
+impl Default for ImagesConfig {
+    fn default() -> Self {
+        Self {
+            widths: Vec::new(),
+            formats: Vec::new(),
+            quality: 80,
+            sizes: "100vw".to_string(),
+        }
+    }
+}
+
+impl ImagesConfig {
+    pub fn enabled(&self) -> bool {
+        !self.widths.is_empty() || !self.formats.is_empty()
+    }
+}
+
+/// Formats images can be transcoded to.
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Webp,
+    Avif,
+}
+
+impl OutputFormat {
+    fn extension(&self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "webp",
+            OutputFormat::Avif => "avif",
+        }
+    }
+
+    fn mime_type(&self) -> &'static str {
+        match self {
+            OutputFormat::Webp => "image/webp",
+            OutputFormat::Avif => "image/avif",
+        }
+    }
+}
+
+/// Resized and transcoded copies of an image, written next to it.
+#[derive(Debug, PartialEq)]
+pub struct ResponsiveImage {
+    /// Intrinsic size of the image.
+    pub width: u32,
+    pub height: u32,
+
+    /// Copies in the image's own format, as file names and widths.
+    pub fallback: Vec<(String, u32)>,
+
+    /// Copies in each of the configured formats.
+    pub sources: Vec<ImageSource>,
+
+    pub sizes: String,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct ImageSource {
+    pub mime_type: &'static str,
+    pub srcset: Vec<(String, u32)>,
+}
+
+impl ResponsiveImage {
+    /// Resize `source` and transcode it as set in `config`, writing the
+    /// copies next to `target`. Copies newer than `source` are kept, as their
+    /// names tell the settings they were written with.
+    ///
+    /// Returns `None` for images that are left as they are: animated GIFs,
+    /// SVGs and formats there is no encoder for.
+    pub fn generate(
+        source: &Path,
+        target: &Path,
+        config: &ImagesConfig,
+    ) -> Result<Option<ResponsiveImage>, ImageError> {
+        let format = match ImageFormat::from_path(source) {
+            Ok(format @ (ImageFormat::Jpeg | ImageFormat::Png | ImageFormat::WebP)) => format,
+            _ => return Ok(None),
+        };
+
+        let (width, height) = oriented_dimensions(source)?;
+        let mut widths: Vec<u32> = config
+            .widths
+            .iter()
+            .copied()
+            .filter(|w| *w > 0 && *w < width)
+            .collect();
+        widths.sort_unstable();
+        widths.dedup();
+        widths.push(width);
+
+        let mut fallback = Vec::new();
+        let mut sources: Vec<ImageSource> = config
+            .formats
+            .iter()
+            .map(|format| ImageSource {
+                mime_type: format.mime_type(),
+                srcset: Vec::new(),
+            })
+            .collect();
+
+        let mut image: Option<DynamicImage> = None;
+        let modified = std::fs::metadata(source)?.modified()?;
+        let quality = config.quality.clamp(1, 100);
+
+        for &w in widths.iter() {
+            let h = ((height as u64 * w as u64 + width as u64 / 2) / width as u64).max(1) as u32;
+
+            // copies in the image's own format, then one per source
+            let mut outputs: Vec<(PathBuf, Option<usize>)> = Vec::new();
+            if w < width {
+                outputs.push((variant_path(target, w, None, format, quality), None));
+            }
+            for (index, output) in config.formats.iter().enumerate() {
+                outputs.push((
+                    variant_path(target, w, Some(*output), format, quality),
+                    Some(index),
+                ));
+            }
+
+            for (path, source_index) in outputs {
+                let up_to_date = std::fs::metadata(&path)
+                    .and_then(|metadata| metadata.modified())
+                    .map(|time| time >= modified)
+                    .unwrap_or(false);
+
+                if !up_to_date {
+                    let decoded = match image {
+                        Some(ref image) => image,
+                        None => image.insert(decode_oriented(source)?),
+                    };
+                    let resized;
+                    let copy = if w < width {
+                        resized = decoded.resize_exact(w, h, FilterType::Lanczos3);
+                        &resized
+                    } else {
+                        decoded
+                    };
+
+                    println!("writing {}", path.display());
+                    let output = source_index.map(|index| config.formats[index]);
+                    write_image(copy, &path, output, format, quality)?;
+                }
+
+                let name = path
+                    .file_name()
+                    .map(|name| name.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                match source_index {
+                    Some(index) => sources[index].srcset.push((name, w)),
+                    None => fallback.push((name, w)),
+                }
+            }
+        }
+
+        let name = target
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or_default();
+        fallback.push((name, width));
+
+        Ok(Some(ResponsiveImage {
+            width,
+            height,
+            fallback,
+            sources,
+            sizes: config.sizes.clone(),
+        }))
+    }
+}
+
+/// Size of the image at `source` as shown, turned as its EXIF orientation
+/// says, without decoding it.
+fn oriented_dimensions(source: &Path) -> Result<(u32, u32), ImageError> {
+    let mut decoder = ImageReader::open(source)?.into_decoder()?;
+    let (width, height) = decoder.dimensions();
+    let turned = matches!(
+        decoder.orientation()?,
+        Orientation::Rotate90
+            | Orientation::Rotate270
+            | Orientation::Rotate90FlipH
+            | Orientation::Rotate270FlipH
+    );
+    Ok(if turned {
+        (height, width)
+    } else {
+        (width, height)
+    })
+}
+
+/// The image at `source`, turned as its EXIF orientation says, as copies
+/// are written without it.
+fn decode_oriented(source: &Path) -> Result<DynamicImage, ImageError> {
+    let mut decoder = ImageReader::open(source)?.into_decoder()?;
+    let orientation = decoder.orientation()?;
+    let mut image = DynamicImage::from_decoder(decoder)?;
+    image.apply_orientation(orientation);
+    Ok(image)
+}
+
+/// Whether a copy in `output`, or in the image's own `format`, is lossy.
+fn is_lossy(output: Option<OutputFormat>, format: ImageFormat) -> bool {
+    matches!(
+        (output, format),
+        (Some(OutputFormat::Avif), _) | (None, ImageFormat::Jpeg)
+    )
+}
+
+/// `photo.jpg` resized to 480 pixels at quality 80 is written as
+/// `photo.jpg.480w.q80.jpg`, or `photo.jpg.480w.q80.avif` in AVIF. Lossless
+/// copies have no quality in their name: `photo.jpg.480w.webp`.
+fn variant_path(
+    target: &Path,
+    width: u32,
+    output: Option<OutputFormat>,
+    format: ImageFormat,
+    quality: u8,
+) -> PathBuf {
+    let extension = match output {
+        Some(output) => output.extension(),
+        None => format.extensions_str()[0],
+    };
+    let quality = if is_lossy(output, format) {
+        format!(".q{quality}")
+    } else {
+        String::new()
+    };
+    let name = target
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+    target.with_file_name(format!("{name}.{width}w{quality}.{extension}"))
+}
+
+fn write_image(
+    image: &DynamicImage,
+    path: &Path,
+    output: Option<OutputFormat>,
+    format: ImageFormat,
+    quality: u8,
+) -> Result<(), ImageError> {
+    let writer = BufWriter::new(File::create(path)?);
+
+    match (output, format) {
+        (Some(OutputFormat::Avif), _) => {
+            let encoder = AvifEncoder::new_with_speed_quality(writer, AVIF_SPEED, quality);
+            DynamicImage::from(image.to_rgba8()).write_with_encoder(encoder)?;
+        }
+        (Some(OutputFormat::Webp), _) | (None, ImageFormat::WebP) => {
+            let encoder = WebPEncoder::new_lossless(writer);
+            DynamicImage::from(image.to_rgba8()).write_with_encoder(encoder)?;
+        }
+        (None, ImageFormat::Jpeg) => {
+            let encoder = JpegEncoder::new_with_quality(writer, quality);
+            DynamicImage::from(image.to_rgb8()).write_with_encoder(encoder)?;
+        }
+        (None, _) => {
+            image.write_with_encoder(PngEncoder::new(writer))?;
+        }
+    }
+
+    Ok(())
+}
+
+#[derive(Error, Debug)]
+pub enum ImageError {
+    #[error("io error")]
+    IOError(#[from] std::io::Error),
+
+    #[error("image error: {0}")]
+    ImageError(#[from] image::ImageError),
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ImageSource, ImagesConfig, OutputFormat, ResponsiveImage};
+
+    #[test]
+    fn test_generate_responsive_image() {
+        let dir = std::env::temp_dir().join(format!("garden-images-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        let source = dir.join("source.png");
+        image::RgbImage::new(8, 4).save(&source).unwrap();
+        let target = dir.join("pic.png");
+        std::fs::copy(&source, &target).unwrap();
+
+        let config = ImagesConfig {
+            widths: vec![4, 16],
+            formats: vec![OutputFormat::Webp],
+            ..Default::default()
+        };
+        let image = ResponsiveImage::generate(&source, &target, &config)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            image,
+            ResponsiveImage {
+                width: 8,
+                height: 4,
+                fallback: vec![
+                    ("pic.png.4w.png".to_string(), 4),
+                    ("pic.png".to_string(), 8)
+                ],
+                sources: vec![ImageSource {
+                    mime_type: "image/webp",
+                    srcset: vec![
+                        ("pic.png.4w.webp".to_string(), 4),
+                        ("pic.png.8w.webp".to_string(), 8)
+                    ],
+                }],
+                sizes: "100vw".to_string(),
+            }
+        );
+        assert_eq!(
+            image::image_dimensions(dir.join("pic.png.4w.webp")).unwrap(),
+            (4, 2)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_apply_exif_orientation() {
+        use image::{codecs::jpeg::JpegEncoder, ImageEncoder};
+
+        let dir = std::env::temp_dir().join(format!("garden-orientation-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+
+        // 8x4 pixels, to be turned 90° clockwise
+        let source = dir.join("photo.jpg");
+        let mut encoder = JpegEncoder::new(std::fs::File::create(&source).unwrap());
+        #[rustfmt::skip]
+        let exif = vec![
+            b'M', b'M', 0, 42, 0, 0, 0, 8,
+            0, 1, 0x01, 0x12, 0, 3, 0, 0, 0, 1, 0, 6, 0, 0,
+            0, 0, 0, 0,
+        ];
+        encoder.set_exif_metadata(exif).unwrap();
+        encoder
+            .write_image(&[0; 8 * 4 * 3], 8, 4, image::ExtendedColorType::Rgb8)
+            .unwrap();
+
+        let config = ImagesConfig {
+            widths: vec![2],
+            formats: vec![OutputFormat::Webp],
+            ..Default::default()
+        };
+        let image = ResponsiveImage::generate(&source, &source, &config)
+            .unwrap()
+            .unwrap();
+
+        assert_eq!((image.width, image.height), (4, 8));
+        assert_eq!(
+            image::image_dimensions(dir.join("photo.jpg.2w.q80.jpg")).unwrap(),
+            (2, 4)
+        );
+        assert_eq!(
+            image::image_dimensions(dir.join("photo.jpg.4w.webp")).unwrap(),
+            (4, 8)
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_skip_unsupported_formats() {
+        let config = ImagesConfig {
+            widths: vec![480],
+            ..Default::default()
+        };
+        let path = std::path::Path::new("drawing.svg");
+        assert_eq!(
+            ResponsiveImage::generate(path, path, &config).unwrap(),
+            None
+        );
+    }
+
+    #[test]
+    fn test_quality_in_lossy_copy_names() {
+        let dir = std::env::temp_dir().join(format!("garden-quality-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let source = dir.join("photo.jpg");
+        image::RgbImage::new(8, 4).save(&source).unwrap();
+
+        let mut config = ImagesConfig {
+            widths: vec![4],
+            formats: vec![OutputFormat::Webp],
+            ..Default::default()
+        };
+        let image = ResponsiveImage::generate(&source, &source, &config)
+            .unwrap()
+            .unwrap();
+        assert_eq!(image.fallback[0].0, "photo.jpg.4w.q80.jpg");
+        assert_eq!(image.sources[0].srcset[0].0, "photo.jpg.4w.webp");
+
+        // other settings are written anew, not taken from the cached copies
+        config.quality = 50;
+        let image = ResponsiveImage::generate(&source, &source, &config)
+            .unwrap()
+            .unwrap();
+        assert_eq!(image.fallback[0].0, "photo.jpg.4w.q50.jpg");
+        assert!(dir.join("photo.jpg.4w.q50.jpg").exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,329 @@
+use pulldown_cmark::escape::escape_html;
+use serde::Deserialize;
+use std::{borrow::Cow, ops::Range};
+
+use crate::{metadata::MetadataValue, obsidian::code_ranges};
+
+const FIELD_SEPARATOR: &str = "::";
+
+/// A Dataview inline field: `key:: value` on a line of its own, or
+/// `[key:: value]` and `(key:: value)` within text.
+#[derive(Debug, PartialEq)]
+pub(crate) struct InlineField {
+    pub key: String,
+    pub value: String,
+
+    /// Source of the whole field, with its brackets.
+    span: Range<usize>,
+
+    /// Source of the value.
+    value_span: Range<usize>,
+
+    /// Bracket the field is written in, `None` for a field on a line.
+    bracket: Option<char>,
+}
+
+/// How inline fields are written in the rendered HTML.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum InlineFieldStyle {
+    /// As written.
+    #[default]
+    Keep,
+
+    /// In `span.inline-field` elements, the key in `span.inline-field-key`
+    /// and the value in `span.inline-field-value`. The key of `(key:: value)`
+    /// fields is left out, as in Obsidian.
+    Style,
+
+    /// Left out, with the lines of line fields.
+    Strip,
+}
+
+impl InlineField {
+    /// Value of the field for the note's `Metadata`.
+    pub fn metadata_value(&self) -> MetadataValue {
+        let value = self.value.trim();
+        if value.is_empty() {
+            MetadataValue::Null
+        } else if let Ok(value) = value.parse::<bool>() {
+            MetadataValue::Boolean(value)
+        } else if let Ok(number) = value.parse::<i64>() {
+            MetadataValue::Integer(number)
+        } else if let Some(number) = value
+            .parse::<f64>()
+            .ok()
+            .filter(|_| value.contains(|c: char| c.is_ascii_digit()))
+        {
+            MetadataValue::Float(number)
+        } else {
+            MetadataValue::from_text(value.to_string())
+        }
+    }
+}
+
+/// Inline fields of a markdown document, outside code.
+pub(crate) fn inline_fields(content: &str) -> Vec<InlineField> {
+    if !content.contains(FIELD_SEPARATOR) {
+        return Vec::new();
+    }
+
+    let code = code_ranges(content);
+    let in_code = |pos: usize| code.iter().any(|range| range.contains(&pos));
+
+    let mut fields = Vec::new();
+    let mut line_start = 0;
+
+    for line in content.split_inclusive('\n') {
+        let text = line.trim_end_matches(['\n', '\r']);
+        let found = fields.len();
+
+        let mut pos = 0;
+        while let Some(offset) = text[pos..].find(['[', '(']) {
+            let open = pos + offset;
+            pos = open + 1;
+            if in_code(line_start + open) {
+                continue;
+            }
+            if let Some(field) = bracket_field(text, open, line_start) {
+                pos = field.span.end - line_start;
+                fields.push(field);
+            }
+        }
+
+        if fields.len() == found {
+            let prefix = line_prefix_len(text);
+            if !in_code(line_start + prefix) {
+                if let Some(field) = line_field(text, prefix, line_start) {
+                    fields.push(field);
+                }
+            }
+        }
+
+        line_start += line.len();
+    }
+
+    fields
+}
+
+/// `[key:: value]` or `(key:: value)` starting at `open` in `text`.
+fn bracket_field(text: &str, open: usize, offset: usize) -> Option<InlineField> {
+    let bracket = text[open..].chars().next()?;
+    let close = if bracket == '[' { ']' } else { ')' };
+
+    let inner_start = open + 1;
+    let separator = inner_start + text[inner_start..].find(FIELD_SEPARATOR)?;
+    let key = field_key(&text[inner_start..separator])?;
+
+    let value_start = separator + FIELD_SEPARATOR.len();
+    let mut depth = 0;
+    let mut value_end = None;
+    for (i, c) in text[value_start..].char_indices() {
+        if c == bracket {
+            depth += 1;
+        } else if c == close {
+            if depth == 0 {
+                value_end = Some(value_start + i);
+                break;
+            }
+            depth -= 1;
+        }
+    }
+    let value_end = value_end?;
+
+    Some(InlineField {
+        key,
+        value: text[value_start..value_end].trim().to_string(),
+        span: offset + open..offset + value_end + 1,
+        value_span: offset + value_start..offset + value_end,
+        bracket: Some(bracket),
+    })
+}
+
+/// `key:: value` after the list marker or quote `prefix` of a line.
+fn line_field(text: &str, prefix: usize, offset: usize) -> Option<InlineField> {
+    let separator = prefix + text[prefix..].find(FIELD_SEPARATOR)?;
+    let key = field_key(&text[prefix..separator])?;
+    let value_start = separator + FIELD_SEPARATOR.len();
+
+    Some(InlineField {
+        key,
+        value: text[value_start..].trim().to_string(),
+        span: offset + prefix..offset + text.len(),
+        value_span: offset + value_start..offset + text.len(),
+        bracket: None,
+    })
+}
+
+/// Key of a field as written before `::`, without bold or italic markers.
+fn field_key(text: &str) -> Option<String> {
+    let key = text.trim().trim_matches(['*', '_']).trim();
+    let valid = !key.is_empty()
+        && key
+            .chars()
+            .all(|c| c.is_alphanumeric() || " -_/".contains(c));
+    valid.then(|| key.to_string())
+}
+
+/// Length of the quote markers, list marker and task box a line starts with.
+fn line_prefix_len(line: &str) -> usize {
+    let mut rest = line.trim_start();
+    loop {
+        let trimmed = rest.strip_prefix('>').map(str::trim_start);
+        match trimmed {
+            Some(trimmed) => rest = trimmed,
+            None => break,
+        }
+    }
+
+    for marker in ["- ", "* ", "+ "] {
+        if let Some(trimmed) = rest.strip_prefix(marker) {
+            rest = trimmed.trim_start();
+            break;
+        }
+    }
+    if let Some((number, trimmed)) = rest.split_once(". ") {
+        if !number.is_empty() && number.chars().all(|c| c.is_ascii_digit()) {
+            rest = trimmed.trim_start();
+        }
+    }
+    for task in ["[ ] ", "[x] ", "[X] "] {
+        if let Some(trimmed) = rest.strip_prefix(task) {
+            rest = trimmed.trim_start();
+            break;
+        }
+    }
+
+    line.len() - rest.len()
+}
+
+/// Key a field can also be read by: lowercase, with spaces replaced by `-`,
+/// as in Dataview.
+pub(crate) fn normalized_key(key: &str) -> String {
+    key.to_lowercase()
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join("-")
+}
+
+/// Markdown with the inline fields written as `style` says.
+pub(crate) fn render_inline_fields(content: &str, style: InlineFieldStyle) -> Cow<'_, str> {
+    if style == InlineFieldStyle::Keep {
+        return Cow::Borrowed(content);
+    }
+
+    let fields = inline_fields(content);
+    if fields.is_empty() {
+        return Cow::Borrowed(content);
+    }
+
+    let mut output = String::with_capacity(content.len());
+    let mut pos = 0;
+
+    for field in fields {
+        match style {
+            InlineFieldStyle::Strip if field.bracket.is_none() => {
+                // the whole line, with its list marker
+                let line_start = content[..field.span.start]
+                    .rfind('\n')
+                    .map(|i| i + 1)
+                    .unwrap_or(0);
+                let line_end = content[field.span.end..]
+                    .find('\n')
+                    .map(|i| field.span.end + i + 1)
+                    .unwrap_or(content.len());
+                output.push_str(&content[pos..line_start]);
+                pos = line_end;
+            }
+            InlineFieldStyle::Strip => {
+                output.push_str(&content[pos..field.span.start]);
+                pos = field.span.end;
+            }
+            _ => {
+                output.push_str(&content[pos..field.span.start]);
+                output.push_str("<span class=\"inline-field\">");
+                if field.bracket != Some('(') {
+                    output.push_str("<span class=\"inline-field-key\">");
+                    escape_html(&mut output, &field.key).unwrap();
+                    output.push_str("</span>");
+                }
+                output.push_str("<span class=\"inline-field-value\">");
+                output.push_str(content[field.value_span.clone()].trim());
+                output.push_str("</span></span>");
+                pos = field.span.end;
+            }
+        }
+    }
+
+    output.push_str(&content[pos..]);
+    Cow::Owned(output)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{inline_fields, render_inline_fields, InlineFieldStyle};
+    use crate::metadata::MetadataValue;
+
+    fn fields(content: &str) -> Vec<(String, String)> {
+        inline_fields(content)
+            .into_iter()
+            .map(|field| (field.key, field.value))
+            .collect()
+    }
+
+    #[test]
+    fn test_inline_fields() {
+        assert_eq!(
+            fields(
+                "Status:: done\n- **Due Date**:: 2024-01-01\n\
+                Text [rating:: 9] and (link:: [[Page]]).\n\
+                `code:: no` [[Page]] [a](b) http://example.com"
+            ),
+            vec![
+                ("Status".to_string(), "done".to_string()),
+                ("Due Date".to_string(), "2024-01-01".to_string()),
+                ("rating".to_string(), "9".to_string()),
+                ("link".to_string(), "[[Page]]".to_string()),
+            ]
+        );
+        assert!(fields("```\nkey:: value\n```").is_empty());
+    }
+
+    #[test]
+    fn test_inline_field_values() {
+        let values: Vec<MetadataValue> = inline_fields("a:: 3\nb:: true\nc:: text\nd::")
+            .iter()
+            .map(|field| field.metadata_value())
+            .collect();
+        assert_eq!(
+            values,
+            vec![
+                MetadataValue::Integer(3),
+                MetadataValue::Boolean(true),
+                MetadataValue::String("text".to_string()),
+                MetadataValue::Null,
+            ]
+        );
+    }
+
+    #[test]
+    fn test_render_inline_fields() {
+        let content = "Status:: *done*\nText [rating:: 9] (hidden:: key).\n";
+        assert_eq!(
+            render_inline_fields(content, InlineFieldStyle::Style),
+            "<span class=\"inline-field\"><span class=\"inline-field-key\">Status</span>\
+            <span class=\"inline-field-value\">*done*</span></span>\n\
+            Text <span class=\"inline-field\"><span class=\"inline-field-key\">rating</span>\
+            <span class=\"inline-field-value\">9</span></span> \
+            <span class=\"inline-field\"><span class=\"inline-field-value\">key</span></span>.\n"
+        );
+        assert_eq!(
+            render_inline_fields(content, InlineFieldStyle::Strip),
+            "Text  .\n"
+        );
+        assert_eq!(
+            render_inline_fields(content, InlineFieldStyle::Keep),
+            content
+        );
+    }
+}
@@ -1,13 +1,33 @@
+mod attachment;
+pub mod callout;
+pub mod canvas;
+mod config;
+mod date;
+pub mod excalidraw;
+mod feed;
+mod images;
+mod inline_field;
+mod manifest;
+mod menu;
+mod mermaid;
 pub mod metadata;
 pub mod note;
+mod obsidian;
+mod query;
+pub mod render;
+mod schema;
+mod search;
 mod site;
 pub mod vault;
 pub mod wikilink;
 
-use clap::{command, Parser, Subcommand};
+use clap::{Parser, Subcommand};
 use rust_embed::RustEmbed;
 use site::Site;
-use std::path::{Path, PathBuf};
+use std::{
+    io::{IsTerminal, Write},
+    path::{Path, PathBuf},
+};
 use vault::VaultBuilder;
 
 fn main() {
@@ -48,42 +68,211 @@ fn main() {
             template,
             tag,
             config: context,
+            clean,
+            force,
+            check,
         } => {
+            let (context, config) = match site::read_context(&context) {
+                Ok(context) => context,
+                Err(err) => {
+                    eprintln!("failed to read {context}: {err}");
+                    std::process::exit(-1);
+                }
+            };
+
+            // both remove files the build did not write
+            if clean || force {
+                let protected = [Path::new(&vault), Path::new(&template)];
+                if let Err(err) = manifest::check_removable(&output_directory, &protected) {
+                    eprintln!("{err}");
+                    std::process::exit(-1);
+                }
+            }
+
             let mut vault_builder = VaultBuilder::new(&vault);
+            vault_builder.exclude_directory(&output_directory);
             if let Some(tags) = tag {
                 vault_builder.filter_tags(tags);
             }
+            vault_builder.metadata_schema(config.schema.clone());
 
             let vault = vault_builder.build();
-            match Site::new(&vault, &template, &output_directory, base_url, &context) {
-                Ok(site) => {
-                    println!("Generating pages...");
-                    for path in vault.notes.keys() {
-                        println!("  {}", path);
-                        site.render_note(path).unwrap();
-                    }
-
-                    let mut source_static_dir = PathBuf::from(&template);
-                    source_static_dir.push("_static");
-                    let mut target_static_dir = PathBuf::from(&output_directory);
-                    target_static_dir.push("_static");
-
-                    if let Err(err) = fsync::sync(source_static_dir, target_static_dir) {
-                        eprintln!("failed to copy _static directory: {err:?}")
-                    }
-
-                    println!("\nOutput directory: {}", &output_directory);
+            // before anything is removed or written
+            if check && vault.problems() > 0 {
+                eprintln!("\n{} problem(s) found, not building.", vault.problems());
+                std::process::exit(-1);
+            }
+
+            if clean {
+                if let Err(err) = clean_output_directory(&output_directory, &config.keep, force) {
+                    eprintln!("{err}");
+                    std::process::exit(-1);
+                }
+            }
+
+            let site = Site::new(
+                &vault,
+                &template,
+                &output_directory,
+                base_url,
+                context,
+                config,
+            );
+
+            println!("Generating pages...");
+            for path in vault.notes.keys() {
+                println!("  {}", path);
+                site.render_note(path).unwrap();
+            }
+            for path in vault.canvases.keys() {
+                println!("  {}", path);
+                if let Err(err) = site.render_canvas(path) {
+                    eprintln!("failed to render {path}: {err:?}")
+                }
+            }
+            for path in vault.drawings.keys() {
+                println!("  {}", path);
+                if let Err(err) = site.render_drawing(path) {
+                    eprintln!("failed to render {path}: {err:?}")
+                }
+            }
+
+            for folder in site.folders() {
+                println!("  {}/", folder.join("/"));
+                if let Err(err) = site.render_folder(folder) {
+                    eprintln!("failed to render folder {}: {err:?}", folder.join("/"))
+                }
+            }
+
+            if let Err(err) = site.copy_attachments() {
+                eprintln!("failed to copy attachments: {err:?}")
+            }
+
+            if let Err(err) = site.write_feed() {
+                eprintln!("failed to write feed: {err:?}")
+            }
+
+            if let Err(err) = site.write_search_index() {
+                eprintln!("failed to write search index: {err:?}")
+            }
+
+            let mut source_static_dir = PathBuf::from(&template);
+            source_static_dir.push("_static");
+            let mut target_static_dir = PathBuf::from(&output_directory);
+            target_static_dir.push("_static");
+
+            if let Err(err) = fsync::sync(source_static_dir, target_static_dir) {
+                eprintln!("failed to copy _static directory: {err:?}")
+            }
+
+            if let Err(err) = site.write_manifest(force) {
+                eprintln!("failed to remove stale files: {err:?}")
+            }
+
+            println!("\nOutput directory: {}", &output_directory);
+        }
+        Commands::Clean {
+            output_directory,
+            config,
+            force,
+        } => {
+            let keep = match config::SiteConfig::from_file(&config) {
+                Ok(config) => config.keep,
+                Err(err) => {
+                    eprintln!("failed to read {config}: {err}");
+                    std::process::exit(-1);
                 }
-                Err(err) => eprintln!("build failed: {err:?}"),
+            };
+
+            // the vault is the current directory, by default
+            let protected = [Path::new("."), Path::new(&config)];
+            if let Err(err) = manifest::check_removable(&output_directory, &protected) {
+                eprintln!("{err}");
+                std::process::exit(-1);
+            }
+
+            if let Err(err) = clean_output_directory(&output_directory, &keep, force) {
+                eprintln!("{err}");
+                std::process::exit(-1);
             }
         }
+        Commands::Check {
+            vault,
+            output_directory,
+            config,
+        } => {
+            let config = match config::SiteConfig::from_file(&config) {
+                Ok(config) => config,
+                Err(err) => {
+                    eprintln!("failed to read {config}: {err}");
+                    std::process::exit(-1);
+                }
+            };
+
+            let mut vault_builder = VaultBuilder::new(&vault);
+            vault_builder.exclude_directory(&output_directory);
+            vault_builder.metadata_schema(config.schema);
+            let vault = vault_builder.build();
+
+            let problems = vault.problems();
+            if problems > 0 {
+                eprintln!("\n{problems} problem(s) found.");
+                std::process::exit(-1);
+            }
+            println!("{} notes checked, no problems found.", vault.notes.len());
+        }
     }
 }
 
+/// Remove the output of previous builds, except the paths in `keep`, from
+/// `site.yaml`.
+///
+/// Directories without the manifest of a previous build are only cleaned
+/// with `force`, or once confirmed on the terminal.
+fn clean_output_directory(
+    output_directory: &str,
+    keep: &[String],
+    force: bool,
+) -> Result<(), String> {
+    if !Path::new(output_directory).exists() {
+        return Ok(());
+    }
+
+    let force = force
+        || manifest::has_manifest(output_directory)
+        || confirm(&format!(
+            "{output_directory} has no manifest of a previous build. Remove everything in it?"
+        ));
+    if !force {
+        return Err(format!(
+            "{output_directory} has no manifest of a previous build, not cleaning it; \
+            use --force to clean it anyway"
+        ));
+    }
+
+    println!("Cleaning {output_directory}");
+    manifest::clean(output_directory, keep, force)
+        .map_err(|err| format!("failed to clean {output_directory}: {err}"))
+}
+
+/// Ask `question` on the terminal. `false` when not run from one.
+fn confirm(question: &str) -> bool {
+    if !std::io::stdin().is_terminal() {
+        return false;
+    }
+
+    print!("{question} [y/N] ");
+    let _ = std::io::stdout().flush();
+    let mut answer = String::new();
+    std::io::stdin().read_line(&mut answer).is_ok()
+        && matches!(answer.trim().to_lowercase().as_str(), "y" | "yes")
+}
+
 fn initialize_config<P: AsRef<Path>>(config_dir: P) -> Result<(), std::io::Error> {
     let default_config = r#"---
 title: Site name
 katex: true
+search: true
 topnav:
   links:
     - text: Link 1
@@ -162,6 +351,51 @@ enum Commands {
 
         #[arg(long, default_value = ".garden/site.yaml")]
         config: String,
+
+        /// Remove the output of previous builds first.
+        #[arg(long)]
+        clean: bool,
+
+        /// Remove every file of the output directory this build did not
+        /// write, and clean it even when it has no manifest of a previous
+        /// build.
+        #[arg(long)]
+        force: bool,
+
+        /// Fail without building when notes can not be read or their
+        /// metadata does not match the `schema`, as `check` does.
+        #[arg(long)]
+        check: bool,
+    },
+
+    /// Check the notes of the vault, such as their metadata against the
+    /// `schema` of the site's configuration.
+    Check {
+        /// Vault directory.
+        #[arg(default_value = ".")]
+        vault: String,
+
+        /// Output directory of builds, left out of the check.
+        #[arg(long, default_value = "./dist")]
+        output_directory: String,
+
+        #[arg(long, default_value = ".garden/site.yaml")]
+        config: String,
+    },
+
+    /// Remove the output of previous builds.
+    Clean {
+        /// Output directory.
+        #[arg(default_value = "./dist")]
+        output_directory: String,
+
+        #[arg(long, default_value = ".garden/site.yaml")]
+        config: String,
+
+        /// Clean even when the output directory has no manifest of a
+        /// previous build.
+        #[arg(long)]
+        force: bool,
     },
 }
 
@@ -0,0 +1,294 @@
+use std::{
+    collections::BTreeSet,
+    fs::File,
+    io::BufWriter,
+    path::{Path, PathBuf},
+};
+use walkdir::WalkDir;
+
+/// Name of the manifest file in the output directory.
+pub(crate) const MANIFEST_FILE: &str = ".garden-manifest.json";
+
+/// Paths written by other tools after the build, kept whatever the `keep`
+/// list of `site.yaml` says.
+const ALWAYS_KEPT: &[&str] = &["_pagefind"];
+
+/// Files written by a build, relative to the output directory.
+///
+/// The manifest of the last build is kept in the output directory, so the
+/// next build can remove the files it no longer writes, such as the pages of
+/// deleted or unpublished notes.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Manifest {
+    files: BTreeSet<PathBuf>,
+}
+
+impl Manifest {
+    /// Manifest of the last build written to `output_directory`, if any.
+    pub fn load<P: AsRef<Path>>(output_directory: P) -> Option<Manifest> {
+        let file = File::open(output_directory.as_ref().join(MANIFEST_FILE)).ok()?;
+        let files: BTreeSet<PathBuf> = serde_json::from_reader(file).ok()?;
+        Some(Manifest { files })
+    }
+
+    /// Every file in `output_directory`, for directories without the
+    /// manifest of a previous build.
+    pub fn from_directory<P: AsRef<Path>>(output_directory: P) -> Manifest {
+        let mut manifest = Manifest::default();
+        manifest.insert_directory(&output_directory, "");
+        manifest.files.remove(Path::new(MANIFEST_FILE));
+        manifest
+    }
+
+    /// Files of `previous` this manifest does not have, except the ones
+    /// under a path in `keep`.
+    pub fn stale<'a>(
+        &'a self,
+        previous: &'a Manifest,
+        keep: &'a [String],
+    ) -> impl Iterator<Item = &'a PathBuf> {
+        previous
+            .files
+            .difference(&self.files)
+            .filter(|path| !is_kept(path, keep) && is_relative(path))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, output_directory: P) -> Result<(), std::io::Error> {
+        let file = File::create(output_directory.as_ref().join(MANIFEST_FILE))?;
+        serde_json::to_writer_pretty(BufWriter::new(file), &self.files)?;
+        Ok(())
+    }
+
+    pub fn insert<P: AsRef<Path>>(&mut self, path: P) {
+        self.files.insert(path.as_ref().to_path_buf());
+    }
+
+    /// Add every file in `directory`, relative to `output_directory`.
+    pub fn insert_directory<P: AsRef<Path>>(&mut self, output_directory: P, directory: &str) {
+        let output_directory = output_directory.as_ref();
+        for entry in WalkDir::new(output_directory.join(directory))
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file())
+        {
+            if let Ok(path) = entry.path().strip_prefix(output_directory) {
+                self.files.insert(path.to_path_buf());
+            }
+        }
+    }
+
+    /// Remove the files of `previous` this manifest does not have, except
+    /// the ones under a path in `keep`, and the directories left empty.
+    /// Returns the removed files.
+    pub fn remove_stale<P: AsRef<Path>>(
+        &self,
+        previous: &Manifest,
+        output_directory: P,
+        keep: &[String],
+    ) -> Result<Vec<PathBuf>, std::io::Error> {
+        let output_directory = output_directory.as_ref();
+        let mut removed = Vec::new();
+
+        for path in self.stale(previous, keep) {
+            match std::fs::remove_file(output_directory.join(path)) {
+                Ok(()) => removed.push(path.clone()),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            }
+
+            let mut parent = path.parent();
+            while let Some(directory) = parent.filter(|dir| !dir.as_os_str().is_empty()) {
+                // fails once a directory is not empty
+                if std::fs::remove_dir(output_directory.join(directory)).is_err() {
+                    break;
+                }
+                parent = directory.parent();
+            }
+        }
+
+        Ok(removed)
+    }
+}
+
+/// Whether `output_directory` has the manifest of a previous build.
+pub(crate) fn has_manifest<P: AsRef<Path>>(output_directory: P) -> bool {
+    output_directory.as_ref().join(MANIFEST_FILE).exists()
+}
+
+/// Error when removing files from `output_directory` could remove one of the
+/// `protected` paths, such as the vault: when the output directory is one of
+/// them or contains them.
+pub(crate) fn check_removable<P: AsRef<Path>>(
+    output_directory: P,
+    protected: &[&Path],
+) -> Result<(), std::io::Error> {
+    // nothing to remove from a directory that does not exist
+    let Ok(output_directory) = output_directory.as_ref().canonicalize() else {
+        return Ok(());
+    };
+
+    for path in protected {
+        if let Ok(path) = path.canonicalize() {
+            if path.starts_with(&output_directory) {
+                return Err(std::io::Error::other(format!(
+                    "{} is or contains {}, not removing files from it",
+                    output_directory.display(),
+                    path.display()
+                )));
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Remove everything in `output_directory` except the paths in `keep`,
+/// leaving an empty manifest.
+///
+/// Only directories with a manifest are cleaned, unless `force` is set, so a
+/// mistyped output directory is left alone.
+pub(crate) fn clean<P: AsRef<Path>>(
+    output_directory: P,
+    keep: &[String],
+    force: bool,
+) -> Result<(), std::io::Error> {
+    let output_directory = output_directory.as_ref();
+    if !force && !has_manifest(output_directory) {
+        return Err(std::io::Error::other(format!(
+            "{} has no {}, not cleaning it",
+            output_directory.display(),
+            MANIFEST_FILE
+        )));
+    }
+
+    for entry in std::fs::read_dir(output_directory)? {
+        let entry = entry?;
+        let path = PathBuf::from(entry.file_name());
+        if is_kept(&path, keep) {
+            continue;
+        }
+
+        if entry.file_type()?.is_dir() {
+            std::fs::remove_dir_all(entry.path())?;
+        } else {
+            std::fs::remove_file(entry.path())?;
+        }
+    }
+
+    // still recognized as an output directory by the next clean
+    Manifest::default().write(output_directory)
+}
+
+/// Whether `path` is, or is in, a path of the keep-list or `ALWAYS_KEPT`.
+fn is_kept(path: &Path, keep: &[String]) -> bool {
+    keep.iter()
+        .map(String::as_str)
+        .chain(ALWAYS_KEPT.iter().copied())
+        .any(|kept| path.starts_with(kept.trim_start_matches("./").trim_end_matches('/')))
+}
+
+/// Manifests are user-editable: never follow paths out of the output
+/// directory.
+fn is_relative(path: &Path) -> bool {
+    path.components()
+        .all(|component| matches!(component, std::path::Component::Normal(_)))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{check_removable, clean, Manifest, MANIFEST_FILE};
+    use std::path::{Path, PathBuf};
+
+    fn output_directory(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("garden-{name}-{}", std::process::id()));
+        let _ = std::fs::remove_dir_all(&dir);
+        std::fs::create_dir_all(dir.join("notes")).unwrap();
+        std::fs::create_dir_all(dir.join("_pagefind")).unwrap();
+        for file in ["index.html", "notes/old.html", "_pagefind/index.js"] {
+            std::fs::write(dir.join(file), "").unwrap();
+        }
+        dir
+    }
+
+    #[test]
+    fn test_remove_stale() {
+        let dir = output_directory("manifest");
+        let mut previous = Manifest::default();
+        for file in [
+            "index.html",
+            "notes/old.html",
+            "_pagefind/index.js",
+            "../outside",
+        ] {
+            previous.insert(file);
+        }
+        let mut manifest = Manifest::default();
+        manifest.insert("index.html");
+
+        let removed = manifest.remove_stale(&previous, &dir, &[]).unwrap();
+
+        assert_eq!(removed, vec![PathBuf::from("notes/old.html")]);
+        assert!(!dir.join("notes").exists());
+        assert!(dir.join("index.html").exists());
+        assert!(dir.join("_pagefind/index.js").exists());
+
+        manifest.write(&dir).unwrap();
+        assert_eq!(Manifest::load(&dir), Some(manifest));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean() {
+        let dir = output_directory("clean");
+        assert!(clean(&dir, &[], false).is_err());
+
+        std::fs::write(dir.join(MANIFEST_FILE), "[]").unwrap();
+        clean(&dir, &["./notes/".to_string()], false).unwrap();
+
+        let mut left: Vec<PathBuf> = std::fs::read_dir(&dir)
+            .unwrap()
+            .map(|entry| entry.unwrap().path())
+            .collect();
+        left.sort();
+        assert_eq!(
+            left,
+            vec![
+                dir.join(MANIFEST_FILE),
+                dir.join("_pagefind"),
+                dir.join("notes")
+            ]
+        );
+        assert!(Path::new(&dir.join("_pagefind/index.js")).exists());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_clean_without_manifest() {
+        let dir = output_directory("clean-force");
+        assert_eq!(Manifest::from_directory(&dir).files.len(), 3);
+
+        clean(&dir, &[], true).unwrap();
+        assert_eq!(
+            Manifest::from_directory(&dir).files,
+            [PathBuf::from("_pagefind/index.js")].into()
+        );
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_check_removable() {
+        let dir = output_directory("removable");
+        let vault = dir.join("notes");
+
+        assert!(check_removable(&dir, &[&vault]).is_err());
+        assert!(check_removable(&vault, &[&vault]).is_err());
+        assert!(check_removable(dir.join("notes/../notes"), &[&vault]).is_err());
+        assert!(check_removable(dir.join("_pagefind"), &[&vault]).is_ok());
+        assert!(check_removable(dir.join("missing"), &[&vault]).is_ok());
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
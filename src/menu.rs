@@ -0,0 +1,356 @@
+use serde::{Deserialize, Serialize};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, HashMap},
+};
+
+use crate::vault::ItemPath;
+
+/// Sidebar menu options from `site.yaml`.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MenuConfig {
+    pub sort: MenuSort,
+
+    /// Show folders collapsed, except the ones with the current page.
+    pub collapsed: bool,
+
+    /// Options of folders, by path.
+    pub folders: HashMap<String, FolderConfig>,
+
+    /// Menu written out, used instead of the one built from the vault.
+    pub tree: Option<Vec<MenuNode>>,
+}
+
+/// How menu entries are ordered. Folders come before pages either way.
+#[derive(Deserialize, Debug, Clone, Copy, Default, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum MenuSort {
+    /// By title.
+    Name,
+
+    /// By the `order` or `weight` of notes' frontmatter, and of folders in
+    /// `folders`, then by title. Entries without one go last.
+    #[default]
+    Order,
+}
+
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct FolderConfig {
+    pub title: Option<String>,
+    pub collapsed: Option<bool>,
+    pub order: Option<f64>,
+}
+
+/// An entry of a menu written out in `site.yaml`: a link to a note, canvas
+/// or drawing, or a group of entries.
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+#[serde(untagged)]
+pub enum MenuNode {
+    Link(String),
+    Group {
+        title: String,
+        #[serde(default)]
+        collapsed: Option<bool>,
+        items: Vec<MenuNode>,
+    },
+}
+
+/// A page to show in the menu.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) struct MenuPage {
+    pub path: ItemPath,
+    pub title: String,
+    pub order: Option<f64>,
+    pub hidden: bool,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(transparent)]
+pub(crate) struct Menu {
+    items: Vec<MenuItem>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+#[serde(tag = "kind", rename_all = "lowercase")]
+pub(crate) enum MenuItem {
+    Page {
+        title: String,
+        path: ItemPath,
+    },
+    Folder {
+        title: String,
+        /// Path of the folder's page, `None` for groups of a written out
+        /// menu.
+        path: Option<String>,
+
+        /// Note next to the folder with the same name, such as
+        /// `Projects.md` next to `Projects/`, linked instead of the folder's
+        /// page.
+        page: Option<ItemPath>,
+        collapsed: bool,
+        items: Vec<MenuItem>,
+    },
+}
+
+impl Menu {
+    /// Menu with the folders and pages of the vault, without hidden pages
+    /// and the folders left empty.
+    pub fn from_pages(pages: Vec<MenuPage>, config: &MenuConfig) -> Self {
+        let mut root = FolderPages::default();
+        for page in pages.into_iter().filter(|page| !page.hidden) {
+            if let ItemPath::Absolute(components) = &page.path {
+                let mut folder = &mut root;
+                for component in &components[..components.len() - 1] {
+                    folder = folder.folders.entry(component.clone()).or_default();
+                }
+                folder.pages.push(page);
+            }
+        }
+
+        Self {
+            items: root.into_items(&[], config),
+        }
+    }
+
+    /// Menu written out in `site.yaml`, with links resolved by `resolve`.
+    /// Links to missing or hidden pages are left out.
+    pub fn from_tree(
+        tree: &[MenuNode],
+        config: &MenuConfig,
+        resolve: &impl Fn(&str) -> Option<MenuPage>,
+    ) -> Self {
+        let items = tree
+            .iter()
+            .filter_map(|node| match node {
+                MenuNode::Link(link) => match resolve(link) {
+                    Some(page) if !page.hidden => Some(MenuItem::Page {
+                        title: page.title,
+                        path: page.path,
+                    }),
+                    Some(_) => None,
+                    None => {
+                        eprintln!("menu entry {link} not found");
+                        None
+                    }
+                },
+                MenuNode::Group {
+                    title,
+                    collapsed,
+                    items,
+                } => Some(MenuItem::Folder {
+                    title: title.clone(),
+                    path: None,
+                    page: None,
+                    collapsed: collapsed.unwrap_or(config.collapsed),
+                    items: Menu::from_tree(items, config, resolve).items,
+                }),
+            })
+            .collect();
+
+        Self { items }
+    }
+}
+
+#[derive(Default)]
+struct FolderPages {
+    pages: Vec<MenuPage>,
+    folders: BTreeMap<String, FolderPages>,
+}
+
+impl FolderPages {
+    fn into_items(mut self, path: &[String], config: &MenuConfig) -> Vec<MenuItem> {
+        let mut folders: Vec<(Option<f64>, MenuItem)> = Vec::new();
+        for (name, folder) in self.folders {
+            let mut folder_path = path.to_vec();
+            folder_path.push(name.clone());
+
+            let items = folder.into_items(&folder_path, config);
+            if items.is_empty() {
+                continue;
+            }
+
+            // the folder note becomes the folder's entry
+            let folder_note = self
+                .pages
+                .iter()
+                .position(|page| page.path.file_name() == Some(name.as_str()))
+                .map(|index| self.pages.remove(index));
+
+            let folder_path = folder_path.join("/");
+            let folder_config = config
+                .folders
+                .get(&folder_path)
+                .cloned()
+                .unwrap_or_default();
+            let (title, order) = match &folder_note {
+                Some(page) => (page.title.clone(), page.order),
+                None => (name, None),
+            };
+            folders.push((
+                folder_config.order.or(order),
+                MenuItem::Folder {
+                    title: folder_config.title.unwrap_or(title),
+                    path: Some(folder_path),
+                    page: folder_note.map(|page| page.path),
+                    collapsed: folder_config.collapsed.unwrap_or(config.collapsed),
+                    items,
+                },
+            ));
+        }
+
+        let mut pages: Vec<(Option<f64>, MenuItem)> = self
+            .pages
+            .into_iter()
+            .map(|page| {
+                let item = MenuItem::Page {
+                    title: page.title,
+                    path: page.path,
+                };
+                (page.order, item)
+            })
+            .collect();
+
+        for entries in [&mut folders, &mut pages] {
+            entries.sort_by(|(order_a, a), (order_b, b)| {
+                let by_order = match config.sort {
+                    MenuSort::Name => Ordering::Equal,
+                    MenuSort::Order => compare_order(*order_a, *order_b),
+                };
+                by_order.then_with(|| compare_titles(a.title(), b.title()))
+            });
+        }
+
+        folders
+            .into_iter()
+            .chain(pages)
+            .map(|(_, item)| item)
+            .collect()
+    }
+}
+
+impl MenuItem {
+    fn title(&self) -> &str {
+        match self {
+            MenuItem::Page { title, .. } | MenuItem::Folder { title, .. } => title,
+        }
+    }
+}
+
+fn compare_order(a: Option<f64>, b: Option<f64>) -> Ordering {
+    match (a, b) {
+        (Some(a), Some(b)) => a.total_cmp(&b),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => Ordering::Equal,
+    }
+}
+
+fn compare_titles(a: &str, b: &str) -> Ordering {
+    a.to_lowercase()
+        .cmp(&b.to_lowercase())
+        .then_with(|| a.cmp(b))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Menu, MenuConfig, MenuItem, MenuNode, MenuPage, MenuSort};
+    use crate::vault::ItemPath;
+
+    fn page(path: &str, order: Option<f64>) -> MenuPage {
+        MenuPage {
+            path: ItemPath::from_path(path),
+            title: path.rsplit('/').next().unwrap().to_string(),
+            order,
+            hidden: false,
+        }
+    }
+
+    fn titles(items: &[MenuItem]) -> Vec<String> {
+        items
+            .iter()
+            .map(|item| match item {
+                MenuItem::Page { title, .. } => title.clone(),
+                MenuItem::Folder { title, items, .. } => {
+                    format!("{title}/[{}]", titles(items).join(", "))
+                }
+            })
+            .collect()
+    }
+
+    fn pages() -> Vec<MenuPage> {
+        vec![
+            page("b", None),
+            page("C", Some(2.0)),
+            page("a", None),
+            page("notes/z", Some(1.0)),
+            page("notes/y", None),
+            MenuPage {
+                hidden: true,
+                ..page("drafts/x", None)
+            },
+        ]
+    }
+
+    #[test]
+    fn test_menu_order() {
+        let menu = Menu::from_pages(pages(), &MenuConfig::default());
+        assert_eq!(titles(&menu.items), vec!["notes/[z, y]", "C", "a", "b"]);
+    }
+
+    #[test]
+    fn test_menu_by_name() {
+        let config: MenuConfig = serde_yaml::from_str(
+            "sort: name\ncollapsed: true\nfolders:\n  notes:\n    title: Notes\n    collapsed: false",
+        )
+        .unwrap();
+        assert_eq!(config.sort, MenuSort::Name);
+
+        let menu = Menu::from_pages(pages(), &config);
+        assert_eq!(titles(&menu.items), vec!["Notes/[y, z]", "a", "b", "C"]);
+        assert!(matches!(
+            &menu.items[0],
+            MenuItem::Folder {
+                path: Some(path),
+                collapsed: false,
+                ..
+            } if path == "notes"
+        ));
+    }
+
+    #[test]
+    fn test_menu_tree() {
+        let tree: Vec<MenuNode> =
+            serde_yaml::from_str("- b\n- title: Group\n  items: [notes/z, missing, drafts/x]")
+                .unwrap();
+        let resolve = |link: &str| {
+            pages()
+                .into_iter()
+                .find(|page| page.path.to_string() == link)
+        };
+
+        let menu = Menu::from_tree(&tree, &MenuConfig::default(), &resolve);
+        assert_eq!(titles(&menu.items), vec!["b", "Group/[z]"]);
+    }
+
+    #[test]
+    fn test_menu_folder_note() {
+        let mut pages = pages();
+        pages.push(MenuPage {
+            title: "My notes".to_string(),
+            ..page("notes", None)
+        });
+        pages.push(page("drafts", None));
+
+        let menu = Menu::from_pages(pages, &MenuConfig::default());
+        assert_eq!(
+            titles(&menu.items),
+            vec!["My notes/[z, y]", "C", "a", "b", "drafts"]
+        );
+        assert!(matches!(
+            &menu.items[0],
+            MenuItem::Folder { page: Some(page), .. } if page.to_string() == "notes"
+        ));
+    }
+}
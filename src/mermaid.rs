@@ -0,0 +1,162 @@
+use pulldown_cmark::{escape::escape_html, CodeBlockKind, Event, Tag};
+use serde::Deserialize;
+use std::{
+    io::Write,
+    process::{Command, Stdio},
+    thread,
+};
+
+/// Mermaid options from `site.yaml`.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MermaidConfig {
+    /// Command that reads a diagram from stdin and writes SVG to stdout, used
+    /// to render diagrams at build time.
+    pub command: Option<Vec<String>>,
+
+    /// URL of the mermaid ES module loaded by pages with diagrams rendered in
+    /// the browser, instead of the one vendored in the template's `_static`
+    /// directory.
+    pub runtime: Option<String>,
+}
+
+/// Replace ```` ```mermaid ```` code blocks with diagrams.
+///
+/// Diagrams are rendered to inline SVG by `command` when one is given,
+/// otherwise (or when the command fails) they are left in a `pre.mermaid`
+/// container for the mermaid runtime. Returns whether any container was left.
+pub(crate) fn render_mermaid<'a>(
+    events: Vec<Event<'a>>,
+    command: Option<&[String]>,
+) -> (Vec<Event<'a>>, bool) {
+    let mut output = Vec::with_capacity(events.len());
+    let mut needs_runtime = false;
+    let mut events = events.into_iter();
+
+    while let Some(event) = events.next() {
+        if !is_mermaid_block(&event) {
+            output.push(event);
+            continue;
+        }
+
+        let mut source = String::new();
+        for event in events.by_ref() {
+            match event {
+                Event::Text(text) => source.push_str(&text),
+                Event::End(Tag::CodeBlock(_)) => break,
+                _ => {}
+            }
+        }
+
+        let svg = command.and_then(|command| match render_svg(command, &source) {
+            Ok(svg) => Some(svg),
+            Err(err) => {
+                eprintln!("failed to render mermaid diagram: {err}");
+                None
+            }
+        });
+
+        let html = match svg {
+            Some(svg) => format!("<div class=\"mermaid-diagram\">{}</div>\n", svg.trim()),
+            None => {
+                needs_runtime = true;
+                let mut html = String::from("<pre class=\"mermaid\">");
+                escape_html(&mut html, &source).unwrap();
+                html.push_str("</pre>\n");
+                html
+            }
+        };
+        output.push(Event::Html(html.into()));
+    }
+
+    (output, needs_runtime)
+}
+
+fn is_mermaid_block(event: &Event) -> bool {
+    match event {
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => {
+            info.split_whitespace().next() == Some("mermaid")
+        }
+        _ => false,
+    }
+}
+
+fn render_svg(command: &[String], source: &str) -> Result<String, std::io::Error> {
+    let (program, args) = command.split_first().ok_or_else(|| {
+        std::io::Error::new(std::io::ErrorKind::InvalidInput, "empty mermaid command")
+    })?;
+
+    let mut child = Command::new(program)
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::inherit())
+        .spawn()?;
+
+    // written from another thread, as the command can fill stdout before it
+    // has read all of stdin
+    let mut stdin = child.stdin.take().expect("piped stdin");
+    let source = source.to_string();
+    let writer = thread::spawn(move || stdin.write_all(source.as_bytes()));
+
+    let output = child.wait_with_output()?;
+    writer.join().expect("stdin writer panicked")?;
+    if !output.status.success() {
+        return Err(std::io::Error::other(format!(
+            "{} exited with {}",
+            program, output.status
+        )));
+    }
+
+    String::from_utf8(output.stdout)
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::render::{render_markdown, RenderOptions};
+
+    #[test]
+    fn test_render_mermaid_container() {
+        let rendered = render_markdown(
+            "```mermaid\ngraph TD\n  A --> B\n```",
+            &RenderOptions::default(),
+        );
+        assert_eq!(
+            rendered.html,
+            "<pre class=\"mermaid\">graph TD\n  A --&gt; B\n</pre>\n"
+        );
+        assert!(rendered.mermaid);
+    }
+
+    #[test]
+    fn test_render_mermaid_command() {
+        let options = RenderOptions {
+            mermaid_command: Some(vec!["cat".to_string()]),
+            ..Default::default()
+        };
+        let rendered = render_markdown("```mermaid\n<svg></svg>\n```", &options);
+        assert_eq!(
+            rendered.html,
+            "<div class=\"mermaid-diagram\"><svg></svg></div>\n"
+        );
+        assert!(!rendered.mermaid);
+    }
+
+    #[test]
+    fn test_render_mermaid_command_large_output() {
+        let options = RenderOptions {
+            mermaid_command: Some(vec!["cat".to_string()]),
+            ..Default::default()
+        };
+        let svg = format!("<svg>{}</svg>", "<g></g>".repeat(64 * 1024));
+        let rendered = render_markdown(&format!("```mermaid\n{svg}\n```"), &options);
+        assert!(rendered.html.contains(&svg));
+    }
+
+    #[test]
+    fn test_render_other_code_block() {
+        let rendered = render_markdown("```rust\nfn main() {}\n```", &RenderOptions::default());
+        assert!(!rendered.mermaid);
+    }
+}
@@ -1,10 +1,31 @@
-use serde::Serialize;
-use std::collections::HashMap;
+use serde::{Serialize, Serializer};
+use std::collections::{HashMap, HashSet};
 use thiserror::Error;
 
-#[derive(Debug, PartialEq, Serialize)]
+use crate::{
+    date::Date,
+    inline_field::{normalized_key, InlineField},
+};
+
+/// Metadata fields with the publication date of a note, by preference.
+pub(crate) const PUBLISHED_FIELDS: [&str; 2] = ["date", "created"];
+
+/// Metadata fields with the date a note was last updated, by preference.
+pub(crate) const UPDATED_FIELDS: [&str; 3] = ["updated", "modified", "last_modified"];
+
+#[derive(Debug, Default, PartialEq, Serialize)]
+#[serde(transparent)]
 pub struct Metadata {
     inner: HashMap<String, MetadataValue>,
+
+    /// Keys added from inline fields, not frontmatter.
+    #[serde(skip)]
+    inline: HashSet<String>,
+
+    /// Frontmatter values read differently than written, such as integers
+    /// too large to be numbers, which are kept as text.
+    #[serde(skip)]
+    warnings: Vec<String>,
 }
 
 impl Metadata {
@@ -27,46 +48,272 @@ impl Metadata {
 
         tags
     }
+
+    /// Keys of the metadata, in no particular order.
+    pub fn keys(&self) -> impl Iterator<Item = &str> {
+        self.inner.keys().map(String::as_str)
+    }
+
+    /// Problems of the frontmatter that did not stop it from being read.
+    pub fn warnings(&self) -> &[String] {
+        &self.warnings
+    }
+
+    /// Whether `key` comes from an inline field of the note's body.
+    pub fn is_inline(&self, key: &str) -> bool {
+        self.inline.contains(key)
+    }
+
+    pub fn get(&self, key: &str) -> Option<&MetadataValue> {
+        self.inner.get(key)
+    }
+
+    /// Value at a dotted `path`, such as `author.name` for the `name` of the
+    /// `author` map, or `links.0` for the first item of the `links` list. A
+    /// key with dots of its own is found too. Tags of tagged values are left
+    /// out.
+    pub fn get_path(&self, path: &str) -> Option<&MetadataValue> {
+        if let Some(value) = self.inner.get(path) {
+            return Some(value.untagged());
+        }
+
+        let mut keys = path.split('.');
+        let mut value = keys.next().and_then(|key| self.inner.get(key));
+        for key in keys {
+            value = match value?.untagged() {
+                MetadataValue::Map(map) => map.get(key),
+                MetadataValue::List(values) => key
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|index| values.get(index)),
+                _ => None,
+            };
+        }
+        value.map(MetadataValue::untagged)
+    }
+
+    /// Text at `path`, such as `category`.
+    pub fn get_str(&self, path: &str) -> Result<Option<&str>, MetadataError> {
+        match self.get_path(path) {
+            Some(MetadataValue::String(value)) => Ok(Some(value)),
+            value => empty_or_type_error(path, value, "text"),
+        }
+    }
+
+    pub fn get_bool(&self, path: &str) -> Result<Option<bool>, MetadataError> {
+        match self.get_path(path) {
+            Some(MetadataValue::Boolean(value)) => Ok(Some(*value)),
+            value => empty_or_type_error(path, value, "boolean"),
+        }
+    }
+
+    /// Integer or float at `path`.
+    pub fn get_number(&self, path: &str) -> Result<Option<f64>, MetadataError> {
+        match self.get_path(path) {
+            Some(MetadataValue::Integer(value)) => Ok(Some(*value as f64)),
+            Some(MetadataValue::Float(value)) => Ok(Some(*value)),
+            value => empty_or_type_error(path, value, "number"),
+        }
+    }
+
+    pub fn get_integer(&self, path: &str) -> Result<Option<i64>, MetadataError> {
+        match self.get_path(path) {
+            Some(MetadataValue::Integer(value)) => Ok(Some(*value)),
+            value => empty_or_type_error(path, value, "integer"),
+        }
+    }
+
+    /// Date or datetime at `path`, such as `date`.
+    pub fn get_date(&self, path: &str) -> Result<Option<Date>, MetadataError> {
+        match self.get_path(path) {
+            Some(MetadataValue::Date(value)) => Ok(Some(*value)),
+            value => empty_or_type_error(path, value, "date"),
+        }
+    }
+
+    pub fn get_list(&self, path: &str) -> Result<Option<&[MetadataValue]>, MetadataError> {
+        match self.get_path(path) {
+            Some(MetadataValue::List(values)) => Ok(Some(values)),
+            value => empty_or_type_error(path, value, "list"),
+        }
+    }
+
+    /// Add the inline fields of a note's body, found with `inline_fields`.
+    /// Frontmatter values win over inline fields of the same key, and a key
+    /// repeated in the body gets a list of its values. Keys are also added
+    /// lowercase with `-` for spaces, so `Due Date` is `due-date` too.
+    pub(crate) fn merge_inline_fields(&mut self, fields: Vec<InlineField>) {
+        let mut inline: Vec<(String, MetadataValue)> = Vec::new();
+        for field in fields {
+            let value = field.metadata_value();
+            match inline.iter_mut().find(|(key, _)| *key == field.key) {
+                Some((_, MetadataValue::List(values))) => values.push(value),
+                Some((_, existing)) => {
+                    let first = std::mem::replace(existing, MetadataValue::Null);
+                    *existing = MetadataValue::List(vec![first, value]);
+                }
+                None => inline.push((field.key, value)),
+            }
+        }
+
+        for (key, value) in inline {
+            let normalized = normalized_key(&key);
+            if normalized != key && !self.inner.contains_key(&normalized) {
+                self.inner.insert(normalized.clone(), value.clone());
+                self.inline.insert(normalized);
+            }
+            if !self.inner.contains_key(&key) {
+                self.inner.insert(key.clone(), value);
+                self.inline.insert(key);
+            }
+        }
+    }
 }
 
 impl From<HashMap<String, MetadataValue>> for Metadata {
     fn from(value: HashMap<String, MetadataValue>) -> Self {
-        Self { inner: value }
+        Self {
+            inner: value,
+            inline: HashSet::new(),
+            warnings: Vec::new(),
+        }
     }
 }
 
-#[derive(Debug, PartialEq, Serialize)]
+/// A value of a note's metadata.
+#[derive(Debug, Clone, PartialEq)]
 pub enum MetadataValue {
     Boolean(bool),
+    Date(Date),
+    Float(f64),
+    Integer(i64),
     List(Vec<MetadataValue>),
     Map(HashMap<String, MetadataValue>),
     Null,
-    Number(f64),
     String(String),
+
+    /// A value with a custom YAML tag, such as `!include file.md`.
+    Tagged {
+        tag: String,
+        value: Box<MetadataValue>,
+    },
+}
+
+/// Serialized as the plain value, for templates to read
+/// `note.metadata.category`. Dates are written `YYYY-MM-DD`, with
+/// `THH:MM:SS` for datetimes, and tagged values without their tag.
+impl Serialize for MetadataValue {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        match self {
+            MetadataValue::Boolean(value) => serializer.serialize_bool(*value),
+            MetadataValue::Date(value) => serializer.collect_str(value),
+            MetadataValue::List(values) => values.serialize(serializer),
+            MetadataValue::Map(map) => map.serialize(serializer),
+            MetadataValue::Null => serializer.serialize_unit(),
+            MetadataValue::Float(value) => serializer.serialize_f64(*value),
+            MetadataValue::Integer(value) => serializer.serialize_i64(*value),
+            MetadataValue::String(value) => serializer.serialize_str(value),
+            MetadataValue::Tagged { value, .. } => value.serialize(serializer),
+        }
+    }
+}
+
+impl MetadataValue {
+    /// Value of unquoted text written in a note, such as an inline field, a
+    /// date if it is one. Daily note links stay links.
+    pub fn from_text(value: String) -> MetadataValue {
+        match value.parse() {
+            Ok(date) if !value.trim_start().starts_with("[[") => MetadataValue::Date(date),
+            _ => MetadataValue::String(value),
+        }
+    }
+
+    /// Value of a date field such as `date`: a date if it is text that is
+    /// one, daily note links included, even when quoted.
+    fn into_date_field(self) -> MetadataValue {
+        match self {
+            MetadataValue::String(value) => match value.parse() {
+                Ok(date) => MetadataValue::Date(date),
+                Err(()) => MetadataValue::String(value),
+            },
+            value => value,
+        }
+    }
+
+    /// The value without its tag, if it has one.
+    pub fn untagged(&self) -> &MetadataValue {
+        match self {
+            MetadataValue::Tagged { value, .. } => value.untagged(),
+            value => value,
+        }
+    }
 }
 
 #[derive(Error, Debug)]
 pub enum MetadataError {
-    #[error("mapping key is not a string")]
-    MetadataMappingKeyTypeError(),
+    #[error("mapping key `{0}` is not a string")]
+    MetadataMappingKeyTypeError(String),
+
+    #[error("invalid YAML in frontmatter at line {line}: {message}")]
+    FrontMatterYamlError { line: usize, message: String },
 
-    #[error("invalid YAML in frontmatter section")]
-    FrontMatterYamlError(#[from] serde_yaml::Error),
+    #[error("invalid TOML in frontmatter at line {line}: {message}")]
+    FrontMatterTomlError { line: usize, message: String },
+
+    #[error("invalid JSON in frontmatter at line {line}: {message}")]
+    FrontMatterJsonError { line: usize, message: String },
+
+    #[error("invalid value of `{key}` in frontmatter at line {line}: {source}")]
+    FrontMatterValueError {
+        line: usize,
+        key: String,
+        source: Box<MetadataError>,
+    },
+
+    #[error("`{key}` is not a {expected}")]
+    MetadataValueTypeError { key: String, expected: &'static str },
 }
 
-impl TryFrom<serde_yaml::Value> for MetadataValue {
-    type Error = MetadataError;
+fn type_error(key: &str, expected: &'static str) -> MetadataError {
+    MetadataError::MetadataValueTypeError {
+        key: key.to_string(),
+        expected,
+    }
+}
 
-    fn try_from(value: serde_yaml::Value) -> Result<Self, Self::Error> {
+/// Missing and null values are no value, others are of the wrong type.
+fn empty_or_type_error<T>(
+    key: &str,
+    value: Option<&MetadataValue>,
+    expected: &'static str,
+) -> Result<Option<T>, MetadataError> {
+    match value {
+        None | Some(MetadataValue::Null) => Ok(None),
+        Some(_) => Err(type_error(key, expected)),
+    }
+}
+
+impl MetadataValue {
+    /// Value of YAML frontmatter. Integers above `i64::MAX` are kept as
+    /// text, with a warning added to `warnings`.
+    fn from_yaml(
+        value: serde_yaml::Value,
+        warnings: &mut Vec<String>,
+    ) -> Result<Self, MetadataError> {
         match value {
             serde_yaml::Value::Null => Ok(MetadataValue::Null),
             serde_yaml::Value::Bool(value) => Ok(MetadataValue::Boolean(value)),
-            serde_yaml::Value::Number(value) => Ok(MetadataValue::Number(value.as_f64().unwrap())),
+            serde_yaml::Value::Number(value) => Ok(MetadataValue::from_number(
+                value.as_i64(),
+                value.as_f64().filter(|_| value.is_f64()),
+                value.to_string(),
+                warnings,
+            )),
             serde_yaml::Value::String(value) => Ok(MetadataValue::String(value)),
             serde_yaml::Value::Sequence(values) => {
                 let mut items = Vec::with_capacity(values.len());
                 for value in values.into_iter() {
-                    items.push(value.try_into()?);
+                    items.push(MetadataValue::from_yaml(value, warnings)?);
                 }
                 Ok(MetadataValue::List(items))
             }
@@ -74,56 +321,422 @@ impl TryFrom<serde_yaml::Value> for MetadataValue {
                 let mut items = HashMap::with_capacity(mapping.len());
                 for (key, value) in mapping.into_iter() {
                     items.insert(
-                        key.as_str()
-                            .ok_or(MetadataError::MetadataMappingKeyTypeError())?
-                            .to_string(),
-                        value.try_into()?,
+                        yaml_key(&key)?.to_string(),
+                        MetadataValue::from_yaml(value, warnings)?,
                     );
                 }
 
                 Ok(MetadataValue::Map(items))
             }
-            serde_yaml::Value::Tagged(_) => todo!(),
+            serde_yaml::Value::Tagged(tagged) => Ok(MetadataValue::Tagged {
+                tag: tagged.tag.to_string(),
+                value: Box::new(MetadataValue::from_yaml(tagged.value, warnings)?),
+            }),
+        }
+    }
+
+    /// Value of JSON frontmatter, with integers above `i64::MAX` kept as
+    /// text as in YAML.
+    fn from_json(value: serde_json::Value, warnings: &mut Vec<String>) -> Self {
+        match value {
+            serde_json::Value::Null => MetadataValue::Null,
+            serde_json::Value::Bool(value) => MetadataValue::Boolean(value),
+            serde_json::Value::Number(value) => MetadataValue::from_number(
+                value.as_i64(),
+                value.as_f64().filter(|_| value.is_f64()),
+                value.to_string(),
+                warnings,
+            ),
+            serde_json::Value::String(value) => MetadataValue::String(value),
+            serde_json::Value::Array(values) => MetadataValue::List(
+                values
+                    .into_iter()
+                    .map(|value| MetadataValue::from_json(value, warnings))
+                    .collect(),
+            ),
+            serde_json::Value::Object(object) => MetadataValue::Map(
+                object
+                    .into_iter()
+                    .map(|(key, value)| (key, MetadataValue::from_json(value, warnings)))
+                    .collect(),
+            ),
+        }
+    }
+
+    /// Integer or float of a number, or its text for integers too large for
+    /// an `i64`, which would lose digits as floats.
+    fn from_number(
+        integer: Option<i64>,
+        float: Option<f64>,
+        text: String,
+        warnings: &mut Vec<String>,
+    ) -> Self {
+        match (integer, float) {
+            (Some(value), _) => MetadataValue::Integer(value),
+            (None, Some(value)) => MetadataValue::Float(value),
+            (None, None) => {
+                warnings.push(format!("integer {text} is too large, read as text"));
+                MetadataValue::String(text)
+            }
+        }
+    }
+}
+
+impl TryFrom<toml::Value> for MetadataValue {
+    type Error = MetadataError;
+
+    fn try_from(value: toml::Value) -> Result<Self, Self::Error> {
+        match value {
+            toml::Value::String(value) => Ok(MetadataValue::String(value)),
+            toml::Value::Integer(value) => Ok(MetadataValue::Integer(value)),
+            toml::Value::Float(value) => Ok(MetadataValue::Float(value)),
+            toml::Value::Boolean(value) => Ok(MetadataValue::Boolean(value)),
+            // times without a date stay text
+            toml::Value::Datetime(value) => Ok(MetadataValue::from_text(value.to_string())),
+            toml::Value::Array(values) => Ok(MetadataValue::List(
+                values
+                    .into_iter()
+                    .map(MetadataValue::try_from)
+                    .collect::<Result<_, _>>()?,
+            )),
+            toml::Value::Table(table) => Ok(MetadataValue::Map(
+                table
+                    .into_iter()
+                    .map(|(key, value)| Ok((key, MetadataValue::try_from(value)?)))
+                    .collect::<Result<_, MetadataError>>()?,
+            )),
         }
     }
 }
 
+/// Split a note into the metadata of its frontmatter and its body.
+///
+/// The frontmatter is at the start of the file, after a UTF-8 byte order
+/// mark if there is one, in one of:
+///
+/// - YAML, from a `---` line to the next `---` or `...` line,
+/// - TOML, from a `+++` line to the next `+++` line,
+/// - JSON, an object whose `{` is alone on the first line.
+///
+/// The closing line can be the last line of the file. Lines end with `\n`
+/// or `\r\n`. A file without frontmatter is all body.
 pub fn parse_frontmatter(content: &str) -> Result<(Metadata, &str), MetadataError> {
-    let mut metadata = HashMap::new();
+    let content = content.strip_prefix('\u{feff}').unwrap_or(content);
 
-    const MARKER: &str = "---\n";
+    if let Some((yaml, body)) = split_frontmatter(content, "---", &["---", "..."]) {
+        Ok((parse_yaml(yaml)?, body))
+    } else if let Some((toml, body)) = split_frontmatter(content, "+++", &["+++"]) {
+        Ok((parse_toml(toml)?, body))
+    } else if content.split_inclusive('\n').next().map(str::trim_end) == Some("{") {
+        parse_json(content)
+    } else {
+        Ok((Metadata::default(), content))
+    }
+}
+
+/// Line numbers in the note of frontmatter between delimiter lines.
+fn line_after_delimiter(line: usize) -> usize {
+    line + 1
+}
+
+fn parse_yaml(yaml: &str) -> Result<Metadata, MetadataError> {
+    let mut value: serde_yaml::Value =
+        serde_yaml::from_str(yaml).map_err(|err| yaml_error(&err, line_after_delimiter(1)))?;
+    // `<<: *defaults` merge keys
+    value
+        .apply_merge()
+        .map_err(|err| yaml_error(&err, line_after_delimiter(1)))?;
+
+    let mapping = match value {
+        serde_yaml::Value::Null => return Ok(Metadata::default()),
+        serde_yaml::Value::Mapping(mapping) => mapping,
+        _ => {
+            return Err(MetadataError::FrontMatterYamlError {
+                line: line_after_delimiter(1),
+                message: "frontmatter is not a mapping of keys to values".to_string(),
+            })
+        }
+    };
 
-    if let Some(content) = content.strip_prefix(MARKER) {
-        if let Some(pos) = content.find(MARKER) {
-            let value: serde_yaml::Value = serde_yaml::from_str(&content[..pos])?;
-            let mapping = value
-                .as_mapping()
-                .ok_or(MetadataError::MetadataMappingKeyTypeError())?;
+    let line_of = |key: &str, from: usize| {
+        line_after_delimiter(key_line_from(yaml, key, ':', from).unwrap_or(from))
+    };
 
-            for (key, value) in mapping.into_iter() {
-                let key = key
-                    .as_str()
-                    .ok_or(MetadataError::MetadataMappingKeyTypeError())?
-                    .to_string();
+    let mut entries = Vec::with_capacity(mapping.len());
+    for (key, value) in mapping.into_iter() {
+        let key = yaml_key(&key)
+            .map_err(|err| key_type_error(err, |key| line_of(key, 1)))?
+            .to_string();
+        // unquoted text is a date if it is one, as YAML timestamps
+        let mut warnings = Vec::new();
+        let value = MetadataValue::from_yaml(value, &mut warnings)
+            .map(|value| match value {
+                MetadataValue::String(text) if is_plain_scalar(yaml, &key) => {
+                    MetadataValue::from_text(text)
+                }
+                value => value,
+            })
+            // keys of nested mappings are searched after the top-level key
+            .map_err(|err| {
+                let from = key_line(yaml, &key, ':').unwrap_or(1);
+                key_type_error(err, |nested| line_of(nested, from))
+            });
+        entries.push((key, value, warnings));
+    }
 
-                let metadata_value = MetadataValue::try_from(value.clone())?;
+    metadata_from_entries(entries, |key| line_of(key, 1))
+}
 
-                metadata.insert(key, metadata_value);
+/// Text of a YAML mapping key, which must be a string.
+fn yaml_key(key: &serde_yaml::Value) -> Result<&str, MetadataError> {
+    key.as_str().ok_or_else(|| {
+        let text = serde_yaml::to_string(key).unwrap_or_default();
+        MetadataError::MetadataMappingKeyTypeError(text.trim_end().to_string())
+    })
+}
+
+/// Mapping key errors as YAML errors at the line of the key, given by
+/// `line_of`. Other errors are left as they are.
+fn key_type_error(err: MetadataError, line_of: impl Fn(&str) -> usize) -> MetadataError {
+    match err {
+        MetadataError::MetadataMappingKeyTypeError(ref key) => {
+            MetadataError::FrontMatterYamlError {
+                line: line_of(key),
+                message: err.to_string(),
             }
+        }
+        err => err,
+    }
+}
 
-            Ok((Metadata::from(metadata), &content[pos + MARKER.len()..]))
-        } else {
-            Ok((Metadata::from(metadata), content))
+fn parse_toml(toml: &str) -> Result<Metadata, MetadataError> {
+    let table: toml::Table = toml.parse().map_err(|err: toml::de::Error| {
+        let line = err
+            .span()
+            .map(|span| toml[..span.start].matches('\n').count() + 1)
+            .unwrap_or(1);
+        MetadataError::FrontMatterTomlError {
+            line: line_after_delimiter(line),
+            message: err.message().to_string(),
         }
-    } else {
-        Ok((Metadata::from(metadata), content))
+    })?;
+
+    let entries = table
+        .into_iter()
+        .map(|(key, value)| (key, MetadataValue::try_from(value), Vec::new()))
+        .collect();
+    metadata_from_entries(entries, |key| {
+        line_after_delimiter(key_line(toml, key, '=').unwrap_or(1))
+    })
+}
+
+/// Metadata of the JSON object at the start of `content`, and the body
+/// after it.
+fn parse_json(content: &str) -> Result<(Metadata, &str), MetadataError> {
+    let json_error = |err: serde_json::Error| MetadataError::FrontMatterJsonError {
+        line: err.line(),
+        message: err
+            .to_string()
+            .split(" at line ")
+            .next()
+            .unwrap_or("")
+            .to_string(),
+    };
+
+    let mut stream =
+        serde_json::Deserializer::from_str(content).into_iter::<serde_json::Map<_, _>>();
+    let object = match stream.next() {
+        Some(result) => result.map_err(json_error)?,
+        None => return Ok((Metadata::default(), content)),
+    };
+
+    let rest = &content[stream.byte_offset()..];
+    let body = match rest.find('\n') {
+        Some(end) if rest[..end].trim().is_empty() => &rest[end + 1..],
+        _ if rest.trim().is_empty() => "",
+        _ => {
+            return Err(MetadataError::FrontMatterJsonError {
+                line: content[..stream.byte_offset()].matches('\n').count() + 1,
+                message: "text after the end of the frontmatter".to_string(),
+            })
+        }
+    };
+
+    let json = &content[..stream.byte_offset()];
+    let entries = object
+        .into_iter()
+        .map(|(key, value)| {
+            let mut warnings = Vec::new();
+            let value = MetadataValue::from_json(value, &mut warnings);
+            (key, Ok(value), warnings)
+        })
+        .collect();
+    let metadata = metadata_from_entries(entries, |key| key_line(json, key, ':').unwrap_or(1))?;
+
+    Ok((metadata, body))
+}
+
+/// Top-level key of frontmatter, its value, and the warnings of reading it.
+type FrontMatterEntry = (String, Result<MetadataValue, MetadataError>, Vec<String>);
+
+/// Metadata of the top-level keys of frontmatter, with the errors and
+/// warnings of their values reported at the line of the key, given by
+/// `line_of`. Text of the date fields is read as dates.
+fn metadata_from_entries(
+    entries: Vec<FrontMatterEntry>,
+    line_of: impl Fn(&str) -> usize,
+) -> Result<Metadata, MetadataError> {
+    let mut metadata = HashMap::with_capacity(entries.len());
+    let mut all_warnings = Vec::new();
+    for (key, value, warnings) in entries {
+        for warning in warnings {
+            all_warnings.push(format!(
+                "`{key}` in frontmatter at line {}: {warning}",
+                line_of(&key)
+            ));
+        }
+        let mut value = value.map_err(|err| match err {
+            // errors with a line of their own, such as of nested keys
+            err @ MetadataError::FrontMatterYamlError { .. } => err,
+            err => MetadataError::FrontMatterValueError {
+                line: line_of(&key),
+                key: key.clone(),
+                source: Box::new(err),
+            },
+        })?;
+        if PUBLISHED_FIELDS.contains(&key.as_str()) || UPDATED_FIELDS.contains(&key.as_str()) {
+            value = value.into_date_field();
+        }
+        metadata.insert(key, value);
     }
+
+    let mut metadata = Metadata::from(metadata);
+    metadata.warnings = all_warnings;
+    Ok(metadata)
+}
+
+/// The text between the `opening` delimiter line and one of the `closing`
+/// lines, and the body after them, if `content` starts with `opening`.
+fn split_frontmatter<'a>(
+    content: &'a str,
+    opening: &str,
+    closing: &[&str],
+) -> Option<(&'a str, &'a str)> {
+    let mut lines = content.split_inclusive('\n');
+    let first = lines.next()?;
+    if first.trim_end() != opening || !first.ends_with('\n') {
+        return None;
+    }
+
+    let start = first.len();
+    let mut pos = start;
+    for line in lines {
+        if closing.contains(&line.trim_end()) {
+            return Some((&content[start..pos], &content[pos + line.len()..]));
+        }
+        pos += line.len();
+    }
+
+    None
+}
+
+/// Line of a top-level `key` followed by `separator` in `text`, from `1`.
+fn key_line(text: &str, key: &str, separator: char) -> Option<usize> {
+    key_line_from(text, key, separator, 1)
+}
+
+/// Line of `key` followed by `separator` in `text`, at or after line `from`,
+/// such as a key of a nested mapping.
+fn key_line_from(text: &str, key: &str, separator: char, from: usize) -> Option<usize> {
+    text.lines()
+        .enumerate()
+        .skip(from - 1)
+        .find(|(_, line)| {
+            let line = line.trim_start().trim_start_matches(['"', '\'']);
+            line.strip_prefix(key).is_some_and(|rest| {
+                rest.trim_start_matches(['"', '\''])
+                    .trim_start()
+                    .starts_with(separator)
+            })
+        })
+        .map(|(index, _)| index + 1)
+}
+
+/// Whether the value of the top-level `key` of `yaml` is written unquoted
+/// on the line of the key, as `date: 2024-03-01`.
+fn is_plain_scalar(yaml: &str, key: &str) -> bool {
+    let Some(line) = key_line(yaml, key, ':') else {
+        return false;
+    };
+    let text = yaml.lines().nth(line - 1).unwrap_or("");
+    let value = text
+        .trim_start()
+        .trim_start_matches(['"', '\''])
+        .strip_prefix(key)
+        .map(|rest| rest.trim_start_matches(['"', '\'']).trim_start())
+        .and_then(|rest| rest.strip_prefix(':'))
+        .map(str::trim_start)
+        .unwrap_or("");
+
+    !value.is_empty() && !value.starts_with(['"', '\'', '[', '{', '|', '>', '!', '&', '*', '#'])
+}
+
+/// Error of YAML starting at line `first_line` of the note.
+fn yaml_error(err: &serde_yaml::Error, first_line: usize) -> MetadataError {
+    let location = err
+        .location()
+        .map(|location| (first_line + location.line() - 1, location.column()));
+    let line = location.map(|(line, _)| line).unwrap_or(first_line);
+
+    // locations in the message are in the frontmatter, not the note
+    let mut message = String::new();
+    let mut rest = err.to_string();
+    while let Some(pos) = rest.find(" at line ") {
+        message.push_str(&rest[..pos]);
+        let after = &rest[pos + " at line ".len()..];
+        rest = match parse_location(after) {
+            Some((line, column, len)) => {
+                let line = first_line + line - 1;
+                // the error's own location is the one reported with it
+                if Some((line, column)) != location {
+                    message.push_str(&format!(" at line {line} column {column}"));
+                }
+                after[len..].to_string()
+            }
+            None => {
+                message.push_str(" at line ");
+                after.to_string()
+            }
+        };
+    }
+    message.push_str(&rest);
+
+    MetadataError::FrontMatterYamlError { line, message }
+}
+
+/// Line and column of `N column M` at the start of `text`, and its length.
+fn parse_location(text: &str) -> Option<(usize, usize, usize)> {
+    let digits = |text: &str| {
+        text.find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(text.len())
+    };
+
+    let line_len = digits(text);
+    let line = text[..line_len].parse().ok()?;
+    let rest = text[line_len..].strip_prefix(" column ")?;
+    let column_len = digits(rest);
+    let column = rest[..column_len].parse().ok()?;
+
+    Some((line, column, text.len() - rest.len() + column_len))
 }
 
 #[cfg(test)]
 mod tests {
     use super::parse_frontmatter;
-    use crate::metadata::{Metadata, MetadataError, MetadataValue};
+    use crate::{
+        date::Date,
+        metadata::{Metadata, MetadataError, MetadataValue},
+    };
     use std::collections::HashMap;
 
     #[test]
@@ -162,19 +775,285 @@ key1: value1
 "#;
         assert!(matches!(
             parse_frontmatter(content).unwrap_err(),
-            MetadataError::FrontMatterYamlError(_)
+            MetadataError::FrontMatterYamlError { line: 2, .. }
         ),);
     }
 
+    #[test]
+    fn test_parse_frontmatter_syntax_error_line() {
+        let content = "---\ntitle: Note\ntags: [a, b\n---\nBody";
+        let err = parse_frontmatter(content).unwrap_err();
+        assert!(
+            matches!(err, MetadataError::FrontMatterYamlError { line: 4, .. }),
+            "{err}"
+        );
+        assert_eq!(
+            err.to_string(),
+            "invalid YAML in frontmatter at line 4: did not find expected ',' or ']', \
+            while parsing a flow sequence at line 3 column 7"
+        );
+    }
+
+    #[test]
+    fn test_parse_frontmatter_crlf() {
+        let (metadata, body) = parse_frontmatter("---\r\ntitle: Note\r\n---\r\nBody\r\n").unwrap();
+        assert_eq!(metadata.get_str("title").unwrap(), Some("Note"));
+        assert_eq!(body, "Body\r\n");
+    }
+
+    #[test]
+    fn test_parse_frontmatter_bom() {
+        let (metadata, body) = parse_frontmatter("\u{feff}---\ntitle: Note\n---\nBody").unwrap();
+        assert_eq!(metadata.get_str("title").unwrap(), Some("Note"));
+        assert_eq!(body, "Body");
+
+        let (_, body) = parse_frontmatter("\u{feff}Body").unwrap();
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn test_parse_frontmatter_dots_terminator() {
+        let (metadata, body) = parse_frontmatter("---\ntitle: Note\n...\nBody").unwrap();
+        assert_eq!(metadata.get_str("title").unwrap(), Some("Note"));
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn test_parse_frontmatter_at_end_of_file() {
+        let (metadata, body) = parse_frontmatter("---\ntitle: Note\n---").unwrap();
+        assert_eq!(metadata.get_str("title").unwrap(), Some("Note"));
+        assert_eq!(body, "");
+    }
+
+    #[test]
+    fn test_parse_frontmatter_empty() {
+        for content in ["---\n---\nBody", "---\n\n# comment\n---\nBody"] {
+            let (metadata, body) = parse_frontmatter(content).unwrap();
+            assert_eq!(metadata, Metadata::default());
+            assert_eq!(body, "Body");
+        }
+    }
+
+    #[test]
+    fn test_parse_frontmatter_not_frontmatter() {
+        // a rule, not frontmatter, when never closed or not first
+        for content in [
+            "---\nNo metadata",
+            "Text\n---\na: 1\n---\n",
+            "----\na: 1\n----\n",
+        ] {
+            assert_eq!(
+                parse_frontmatter(content).unwrap(),
+                (Metadata::default(), content)
+            );
+        }
+        // only whole lines close the frontmatter
+        let (metadata, body) = parse_frontmatter("---\na: x---\n---\nBody").unwrap();
+        assert_eq!(metadata.get_str("a").unwrap(), Some("x---"));
+        assert_eq!(body, "Body");
+    }
+
+    #[test]
+    fn test_parse_frontmatter_not_a_mapping() {
+        assert!(matches!(
+            parse_frontmatter("---\n- a\n- b\n---\n").unwrap_err(),
+            MetadataError::FrontMatterYamlError { line: 2, .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_frontmatter_toml() {
+        let content =
+            "+++\r\ntitle = \"Note\"\ndate = 2024-03-01\nupdated = 2024-03-02T10:30:00Z\n\
+            count = 3\ntags = [\"a\"]\n[author]\nname = \"Ada\"\n+++\nBody";
+        let (metadata, body) = parse_frontmatter(content).unwrap();
+        let (yaml, _) = parse_frontmatter(
+            "---\ntitle: Note\ndate: 2024-03-01\nupdated: 2024-03-02T10:30:00Z\n\
+            count: 3\ntags: [a]\nauthor:\n  name: Ada\n---\n",
+        )
+        .unwrap();
+        assert_eq!(metadata, yaml);
+        assert_eq!(metadata.tags(), vec!["a"]);
+        assert_eq!(body, "Body");
+
+        assert!(matches!(
+            parse_frontmatter("+++\ntitle = \"Note\"\ncount = \n+++\n").unwrap_err(),
+            MetadataError::FrontMatterTomlError { line: 3, .. }
+        ));
+    }
+
+    #[test]
+    fn test_parse_frontmatter_json() {
+        let content = "{\n  \"title\": \"Note\",\n  \"date\": \"2024-03-01\",\n  \
+            \"count\": 3,\n  \"tags\": [\"a\"],\n  \"author\": {\"name\": \"Ada\"}\n}\nBody";
+        let (metadata, body) = parse_frontmatter(content).unwrap();
+        let (yaml, _) = parse_frontmatter(
+            "---\ntitle: Note\ndate: 2024-03-01\ncount: 3\ntags: [a]\nauthor:\n  name: Ada\n---\n",
+        )
+        .unwrap();
+        assert_eq!(metadata, yaml);
+        assert_eq!(body, "Body");
+
+        assert!(matches!(
+            parse_frontmatter("{\n  \"title\": \"Note\"\n  \"count\": 3\n}\n").unwrap_err(),
+            MetadataError::FrontMatterJsonError { line: 3, .. }
+        ));
+        let (metadata, _) =
+            parse_frontmatter("{\n  \"title\": \"Note\",\n  \"id\": 18446744073709551615\n}\n")
+                .unwrap();
+        assert_eq!(
+            metadata.get_str("id").unwrap(),
+            Some("18446744073709551615")
+        );
+        assert_eq!(
+            metadata.warnings(),
+            ["`id` in frontmatter at line 3: integer 18446744073709551615 is too large, read as text"]
+        );
+
+        // not frontmatter unless `{` is alone on the first line
+        let content = "{{ template }}\nBody";
+        assert_eq!(
+            parse_frontmatter(content).unwrap(),
+            (Metadata::default(), content)
+        );
+    }
+
     #[test]
     fn test_parse_frontmatter_mapping_key_is_not_string() {
         let content = r#"---
 42: oops
 ---
 "#;
+        assert_eq!(
+            parse_frontmatter(content).unwrap_err().to_string(),
+            "invalid YAML in frontmatter at line 2: mapping key `42` is not a string"
+        );
+
+        let content = "---\ntitle: Note\npage:\n  title: Page\n  2024: oops\n---\n";
+        assert_eq!(
+            parse_frontmatter(content).unwrap_err().to_string(),
+            "invalid YAML in frontmatter at line 5: mapping key `2024` is not a string"
+        );
+    }
+
+    #[test]
+    fn test_parse_frontmatter_yaml_values() {
+        let content = "---\nid: 9007199254740993\nratio: 0.5\n\
+            defaults: &defaults\n  lang: en\npage:\n  <<: *defaults\n  title: Page\n\
+            include: !include [a.md]\nlogo: !!binary aGVsbG8=\n---\n";
+        let (metadata, _) = parse_frontmatter(content).unwrap();
+        assert_eq!(
+            metadata.get("id"),
+            Some(&MetadataValue::Integer(9007199254740993))
+        );
+        assert_eq!(metadata.get_number("ratio").unwrap(), Some(0.5));
+        assert!(metadata.get_integer("ratio").is_err());
+        assert_eq!(metadata.get_str("page.lang").unwrap(), Some("en"));
+        assert_eq!(
+            metadata.get("include"),
+            Some(&MetadataValue::Tagged {
+                tag: "!include".to_string(),
+                value: Box::new(MetadataValue::List(vec![MetadataValue::String(
+                    "a.md".to_string()
+                )]))
+            })
+        );
+        assert_eq!(metadata.get_list("include").unwrap().unwrap().len(), 1);
+        assert_eq!(metadata.get_str("logo").unwrap(), Some("aGVsbG8="));
+
+        assert!(metadata.warnings().is_empty());
+
+        // kept as text, without losing the note
+        let (metadata, _) =
+            parse_frontmatter("---\ntitle: Big\nids: [1, 18446744073709551615]\n---\n").unwrap();
+        assert_eq!(
+            metadata.get("ids"),
+            Some(&MetadataValue::List(vec![
+                MetadataValue::Integer(1),
+                MetadataValue::String("18446744073709551615".to_string())
+            ]))
+        );
+        assert_eq!(
+            metadata.warnings(),
+            ["`ids` in frontmatter at line 3: integer 18446744073709551615 is too large, read as text"]
+        );
+    }
+
+    #[test]
+    fn test_parse_frontmatter_dates() {
+        let content = "---\ntitle: \"2024-03-01\"\nup: \"[[2024-03-01]]\"\n\
+            due: 2024-03-02\ncreated: \"[[2024-03-01]]\"\nupdated: '2024-03-03'\n\
+            events: [2024-03-04]\n---\n";
+        let (metadata, _) = parse_frontmatter(content).unwrap();
+        let date = |value: &str| Some(MetadataValue::Date(value.parse().unwrap()));
+
+        assert_eq!(metadata.get_str("title").unwrap(), Some("2024-03-01"));
+        assert_eq!(metadata.get_str("up").unwrap(), Some("[[2024-03-01]]"));
+        assert_eq!(metadata.get("due").cloned(), date("2024-03-02"));
+        assert_eq!(metadata.get("created").cloned(), date("2024-03-01"));
+        assert_eq!(metadata.get("updated").cloned(), date("2024-03-03"));
+        assert_eq!(metadata.get_str("events.0").unwrap(), Some("2024-03-04"));
+
+        let (metadata, _) =
+            parse_frontmatter("{\n  \"due\": \"2024-03-02\",\n  \"date\": \"2024-03-01\"\n}\n")
+                .unwrap();
+        assert_eq!(metadata.get_str("due").unwrap(), Some("2024-03-02"));
+        assert_eq!(metadata.get("date").cloned(), date("2024-03-01"));
+    }
+
+    #[test]
+    fn test_metadata_getters() {
+        let content = "---\ndate: 2024-05-01\ndraft: true\nsubtitle:\n\
+            author:\n  name: Ada\n  links: [a, b]\nupdated: 2024-02-30\n---\n";
+        let (metadata, _) = parse_frontmatter(content).unwrap();
+        assert!(metadata.get_str("date").is_err());
+        assert_eq!(metadata.get_str("missing").unwrap(), None);
+        assert_eq!(metadata.get_str("subtitle").unwrap(), None);
         assert!(matches!(
-            parse_frontmatter(content).unwrap_err(),
-            MetadataError::MetadataMappingKeyTypeError()
-        ),);
+            metadata.get_str("draft"),
+            Err(MetadataError::MetadataValueTypeError { key, expected: "text" }) if key == "draft"
+        ));
+        assert_eq!(metadata.get_bool("draft").unwrap(), Some(true));
+        assert!(metadata.get_number("date").is_err());
+        assert_eq!(
+            metadata.get_date("date").unwrap(),
+            Some(Date {
+                year: 2024,
+                month: 5,
+                day: 1,
+                time: None,
+                offset: None
+            })
+        );
+        assert_eq!(
+            metadata.get("updated"),
+            Some(&MetadataValue::String("2024-02-30".to_string()))
+        );
+        assert!(metadata.get_date("updated").is_err());
+        assert_eq!(metadata.get_str("author.name").unwrap(), Some("Ada"));
+        assert_eq!(metadata.get_str("author.links.1").unwrap(), Some("b"));
+        assert_eq!(metadata.get_list("author.links").unwrap().unwrap().len(), 2);
+        assert_eq!(metadata.get_path("author.missing"), None);
+    }
+
+    #[test]
+    fn test_metadata_serialize() {
+        let content = "---\ncategory: Example\ncount: 3\nratio: 0.5\ntags: [a]\n\
+            date: 2024-03-01\nupdated: 2024-03-02 10:30\n\
+            author:\n  name: Ada\nempty:\n---\n";
+        let (metadata, _) = parse_frontmatter(content).unwrap();
+        assert_eq!(
+            serde_json::to_value(&metadata).unwrap(),
+            serde_json::json!({
+                "category": "Example",
+                "count": 3,
+                "ratio": 0.5,
+                "tags": ["a"],
+                "date": "2024-03-01",
+                "updated": "2024-03-02T10:30:00",
+                "author": {"name": "Ada"},
+                "empty": null,
+            })
+        );
     }
 }
@@ -1,11 +1,15 @@
-use pulldown_cmark::Event;
+use pulldown_cmark::{Event, LinkType, Tag};
 use serde::Serialize;
 use std::path::Path;
 use thiserror::Error;
 
 use crate::{
+    inline_field::inline_fields,
     metadata::{parse_frontmatter, Metadata, MetadataError},
-    wikilink::{Wikilink, WikilinkParser},
+    obsidian::strip_comments,
+    query::is_query_block,
+    render::{render_markdown_with_links, LinkResolver, RenderOptions, RenderedHtml},
+    wikilink::{parse_with_wikilinks, Wikilink},
 };
 
 #[derive(Debug, PartialEq, Serialize)]
@@ -14,34 +18,49 @@ pub struct Note {
     pub content: String,
     pub tags: Vec<String>,
     pub links: Vec<Wikilink>,
+
+    /// Destinations of markdown links and images that point into the vault,
+    /// as written.
+    pub markdown_links: Vec<String>,
     pub metadata: Metadata,
 }
 
 impl Note {
     pub fn parse(title: &str, content: &str) -> Result<Note, NoteError> {
-        let (metadata, content) = parse_frontmatter(content)?;
-
-        let parser = pulldown_cmark::Parser::new(content);
+        let (mut metadata, content) = parse_frontmatter(content)?;
+        let content = strip_comments(content);
+        metadata.merge_inline_fields(inline_fields(&content));
 
         let mut links = Vec::new();
+        let mut markdown_links = Vec::new();
         let mut tags = metadata.tags();
 
-        let mut wikilink_parser = WikilinkParser::new();
-        for event in parser {
-            if let Event::Text(text) = event {
-                if let Some(link) = wikilink_parser.feed(&text) {
-                    links.push(link);
+        let events = parse_with_wikilinks(&content, |wikilink, _| {
+            links.push(wikilink.clone());
+            vec![]
+        });
+        // the `FROM #tag` of a query does not tag the note
+        let mut in_query = false;
+        for event in events {
+            match event {
+                ref event if is_query_block(event) => in_query = true,
+                Event::End(Tag::CodeBlock(_)) => in_query = false,
+                Event::Text(text) if !in_query => collect_tags(&text, &mut tags),
+                Event::Start(Tag::Link(link_type, dest, _) | Tag::Image(link_type, dest, _))
+                    if link_type != LinkType::Email && is_local_link(&dest) =>
+                {
+                    markdown_links.push(dest.to_string());
                 }
-
-                collect_tags(&text, &mut tags);
+                _ => {}
             }
         }
 
         Ok(Note {
             title: title.into(),
-            content: content.into(),
+            content,
             tags,
             links,
+            markdown_links,
             metadata,
         })
     }
@@ -56,18 +75,38 @@ impl Note {
         Note::parse(&title, &content)
     }
 
-    pub fn render_html(&self) -> String {
-        let mut html_buf = String::new();
-        let parser = pulldown_cmark::Parser::new_ext(&self.content, pulldown_cmark::Options::all());
-        pulldown_cmark::html::push_html(&mut html_buf, parser);
-        html_buf
+    pub fn render_html(
+        &self,
+        options: &RenderOptions,
+        links: Option<&dyn LinkResolver>,
+    ) -> RenderedHtml {
+        render_markdown_with_links(&self.content, options, links)
     }
 }
 
+/// Whether a markdown link destination points into the vault rather than to
+/// a URL or a heading of the same note.
+pub(crate) fn is_local_link(dest: &str) -> bool {
+    let scheme = dest
+        .split_once(':')
+        .map(|(scheme, _)| {
+            scheme
+                .chars()
+                .next()
+                .is_some_and(|c| c.is_ascii_alphabetic())
+                && scheme
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "+-.".contains(c))
+        })
+        .unwrap_or(false);
+
+    !dest.is_empty() && !dest.starts_with('#') && !dest.starts_with("//") && !scheme
+}
+
 fn collect_tags(text: &str, tags: &mut Vec<String>) {
     let mut tag_start = 0;
 
-    for (i, chr) in text.chars().enumerate() {
+    for (i, chr) in text.char_indices() {
         if chr == '#' {
             tag_start = i + 1;
         } else if tag_start > 0
@@ -90,7 +129,7 @@ pub enum NoteError {
 
     // #[error("invalid frontmatter type")]
     // FrontMatterInvalidType(),
-    #[error("frontmatter value error")]
+    #[error("{0}")]
     MetadataValueError(#[from] MetadataError),
 
     #[error("no file name")]
@@ -149,8 +188,67 @@ fn main () {
                     Wikilink::new("Page Name", Some("Link label")),
                     Wikilink::new("WikiLink", None),
                 ],
+                markdown_links: vec![],
                 metadata: Metadata::from(metadata),
             }
         );
     }
+
+    #[test]
+    fn test_parse_note_markdown_links() {
+        let note = Note::parse(
+            "Note",
+            "[a](Other%20Note.md#Intro) ![](attachments/pic.png) [b](https://example.com) \
+            [c](#Heading) <mail@example.com> [d](mailto:mail@example.com)",
+        )
+        .unwrap();
+        assert_eq!(
+            note.markdown_links,
+            vec!["Other%20Note.md#Intro", "attachments/pic.png"]
+        );
+    }
+
+    #[test]
+    fn test_parse_note_strips_comments() {
+        let note = Note::parse("Note", "Public %%private #secret [[Hidden]]%% text").unwrap();
+        assert_eq!(note.content, "Public  text");
+        assert!(note.tags.is_empty());
+        assert!(note.links.is_empty());
+    }
+
+    #[test]
+    fn test_parse_note_query_tags() {
+        let note = Note::parse("Note", "#index\n\n```dataview\nLIST FROM #project\n```").unwrap();
+        assert_eq!(note.tags, vec!["index"]);
+    }
+
+    #[test]
+    fn test_parse_note_inline_fields() {
+        let note = Note::parse(
+            "Note",
+            "---\nstatus: draft\n---\nStatus:: done\n- [tag:: a] and [tag:: b]\n\
+            **Due Date**:: 2024-01-01\n```\nrating:: 1\n```",
+        )
+        .unwrap();
+        assert_eq!(note.metadata.get_str("status").unwrap(), Some("draft"));
+        assert_eq!(note.metadata.get_str("Status").unwrap(), Some("done"));
+        assert_eq!(
+            note.metadata.get_date("due-date").unwrap(),
+            "2024-01-01".parse().ok()
+        );
+        assert_eq!(
+            note.metadata.get("tag"),
+            Some(&MetadataValue::List(vec![
+                MetadataValue::String("a".to_string()),
+                MetadataValue::String("b".to_string()),
+            ]))
+        );
+        assert_eq!(note.metadata.get("rating"), None);
+    }
+
+    #[test]
+    fn test_parse_note_wikilink_label_with_strikethrough() {
+        let note = Note::parse("Note", "[[Page|~~old~~ new]]").unwrap();
+        assert_eq!(note.links, vec![Wikilink::new("Page", Some("~~old~~ new"))]);
+    }
 }
@@ -0,0 +1,288 @@
+use pulldown_cmark::{escape::escape_html, CowStr, Event, Tag};
+use std::ops::Range;
+
+const COMMENT_MARKER: &str = "%%";
+const HIGHLIGHT_MARKER: &str = "==";
+const STRIKETHROUGH_MARKER: &str = "~~";
+
+const MARK_OPEN: &str = "<mark>";
+const MARK_CLOSE: &str = "</mark>";
+
+/// Remove `%%comment%%` blocks from a markdown document.
+///
+/// Markers inside code spans and code blocks are kept. As in Obsidian, an
+/// unterminated comment hides the rest of the document.
+pub(crate) fn strip_comments(content: &str) -> String {
+    if !content.contains(COMMENT_MARKER) {
+        return content.to_string();
+    }
+
+    let code = code_ranges(content);
+    let in_code = |pos: usize| code.iter().any(|range| range.contains(&pos));
+
+    let mut output = String::with_capacity(content.len());
+    let mut start = 0;
+    let mut comment_start: Option<usize> = None;
+    let mut pos = 0;
+
+    while let Some(found) = content[pos..].find(COMMENT_MARKER) {
+        let marker = pos + found;
+        pos = marker + COMMENT_MARKER.len();
+
+        if in_code(marker) {
+            continue;
+        }
+
+        match comment_start.take() {
+            None => {
+                output.push_str(&content[start..marker]);
+                comment_start = Some(marker);
+            }
+            Some(_) => start = pos,
+        }
+    }
+
+    if comment_start.is_none() {
+        output.push_str(&content[start..]);
+    }
+
+    output
+}
+
+/// Source ranges of code spans and code blocks.
+pub(crate) fn code_ranges(content: &str) -> Vec<Range<usize>> {
+    pulldown_cmark::Parser::new_ext(content, pulldown_cmark::Options::all())
+        .into_offset_iter()
+        .filter_map(|(event, range)| match event {
+            Event::Code(_) | Event::Start(Tag::CodeBlock(_)) => Some(range),
+            _ => None,
+        })
+        .collect()
+}
+
+/// Render `==highlight==` as `<mark>`.
+///
+/// A highlight left open at the end of a block is written back as text.
+pub(crate) fn render_highlights(events: Vec<Event>) -> Vec<Event> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut open: Option<usize> = None;
+    let mut in_code_block = false;
+
+    for event in events {
+        match event {
+            Event::Text(text) if !in_code_block && text.contains(HIGHLIGHT_MARKER) => {
+                let mut parts = text.split(HIGHLIGHT_MARKER).peekable();
+                while let Some(part) = parts.next() {
+                    if !part.is_empty() {
+                        output.push(Event::Text(part.to_string().into()));
+                    }
+                    if parts.peek().is_none() {
+                        break;
+                    }
+                    match open.take() {
+                        Some(_) => output.push(Event::Html(MARK_CLOSE.into())),
+                        None => {
+                            open = Some(output.len());
+                            output.push(Event::Html(MARK_OPEN.into()));
+                        }
+                    }
+                }
+            }
+            Event::Start(Tag::CodeBlock(_)) => {
+                in_code_block = true;
+                output.push(event);
+            }
+            Event::End(Tag::CodeBlock(_)) => {
+                in_code_block = false;
+                output.push(event);
+            }
+            Event::End(
+                Tag::Paragraph
+                | Tag::Heading(..)
+                | Tag::TableCell
+                | Tag::Item
+                | Tag::FootnoteDefinition(_),
+            ) => {
+                if let Some(index) = open.take() {
+                    output[index] = Event::Text(HIGHLIGHT_MARKER.into());
+                }
+                output.push(event);
+            }
+            event => output.push(event),
+        }
+    }
+
+    output
+}
+
+/// Turn `^[inline footnotes]` into footnote references, with their
+/// definitions appended to the end of the document.
+pub(crate) fn render_inline_footnotes(events: Vec<Event>) -> Vec<Event> {
+    let mut output: Vec<Event> = Vec::with_capacity(events.len());
+    let mut definitions: Vec<Event> = Vec::new();
+    let mut count = 0;
+    let mut events = events.into_iter().peekable();
+
+    while let Some(event) = events.next() {
+        let is_start = matches!(&event, Event::Text(text) if text.ends_with('^'))
+            && events.peek() == Some(&Event::Text(CowStr::Borrowed("[")));
+        if !is_start {
+            output.push(event);
+            continue;
+        }
+
+        let opening = events.next().unwrap();
+        let mut depth = 1;
+        let mut body = Vec::new();
+        let mut block_end = None;
+        for event in events.by_ref() {
+            match &event {
+                Event::Text(text) if &**text == "[" => depth += 1,
+                Event::Text(text) if &**text == "]" => depth -= 1,
+                Event::End(Tag::Paragraph | Tag::Heading(..) | Tag::TableCell | Tag::Item) => {
+                    block_end = Some(event);
+                    break;
+                }
+                _ => {}
+            }
+            if depth == 0 {
+                break;
+            }
+            body.push(event);
+        }
+
+        if let Event::Text(text) = event {
+            let text = &text[..text.len() - 1];
+            if !text.is_empty() {
+                output.push(Event::Text(text.to_string().into()));
+            }
+        }
+
+        if depth > 0 {
+            // no closing bracket: keep the text as written
+            output.push(Event::Text("^".into()));
+            output.push(opening);
+            output.extend(body);
+            output.extend(block_end);
+            continue;
+        }
+
+        count += 1;
+        let label: CowStr = format!("inline-{count}").into();
+        output.push(Event::FootnoteReference(label.clone()));
+        definitions.push(Event::Start(Tag::FootnoteDefinition(label.clone())));
+        definitions.push(Event::Start(Tag::Paragraph));
+        definitions.extend(body);
+        definitions.push(Event::End(Tag::Paragraph));
+        definitions.push(Event::End(Tag::FootnoteDefinition(label)));
+    }
+
+    output.extend(definitions);
+    output
+}
+
+/// HTML for a wikilink label, rendering `~~strikethrough~~` and
+/// `==highlight==`.
+pub(crate) fn render_label(label: &str) -> String {
+    let mut html = String::new();
+    escape_html(&mut html, label).unwrap();
+
+    for (marker, open, close) in [
+        (STRIKETHROUGH_MARKER, "<del>", "</del>"),
+        (HIGHLIGHT_MARKER, MARK_OPEN, MARK_CLOSE),
+    ] {
+        let mut parts: Vec<&str> = html.split(marker).collect();
+        if parts.len() < 3 {
+            continue;
+        }
+        // an unpaired marker is kept as text
+        let unpaired = parts.len().is_multiple_of(2);
+        let last = if unpaired { parts.pop() } else { None };
+
+        let mut rendered = String::new();
+        for (i, part) in parts.iter().enumerate() {
+            if i > 0 {
+                rendered.push_str(if i % 2 == 1 { open } else { close });
+            }
+            rendered.push_str(part);
+        }
+        if let Some(last) = last {
+            rendered.push_str(marker);
+            rendered.push_str(last);
+        }
+        html = rendered;
+    }
+
+    html
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{render_label, strip_comments};
+    use crate::render::{render_markdown, RenderOptions};
+
+    #[test]
+    fn test_strip_comments() {
+        assert_eq!(strip_comments("a %%hidden%% b"), "a  b");
+        assert_eq!(
+            strip_comments("a\n\n%%\nsecret\n\nblock\n%%\n\nb"),
+            "a\n\n\n\nb"
+        );
+        assert_eq!(strip_comments("a `%%code%%` b"), "a `%%code%%` b");
+        assert_eq!(strip_comments("a %%unterminated\n\nb"), "a ");
+    }
+
+    #[test]
+    fn test_render_highlight() {
+        let html = render_markdown("a ==high **b** c== d", &RenderOptions::default()).html;
+        assert_eq!(html, "<p>a <mark>high <strong>b</strong> c</mark> d</p>\n");
+    }
+
+    #[test]
+    fn test_render_unclosed_highlight() {
+        let html = render_markdown("a ==b\n\nc", &RenderOptions::default()).html;
+        assert_eq!(html, "<p>a ==b</p>\n<p>c</p>\n");
+    }
+
+    #[test]
+    fn test_render_highlight_in_code() {
+        let html = render_markdown("`==a==`\n\n```\n==b==\n```", &RenderOptions::default()).html;
+        assert_eq!(
+            html,
+            "<p><code>==a==</code></p>\n<pre><code>==b==\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_inline_footnote() {
+        let html = render_markdown("a^[note *b* [c]] d", &RenderOptions::default()).html;
+        assert_eq!(
+            html,
+            "<p>a<sup class=\"footnote-reference\"><a href=\"#inline-1\">1</a></sup> d</p>\n\
+            <div class=\"footnote-definition\" id=\"inline-1\">\
+            <sup class=\"footnote-definition-label\">1</sup>\n\
+            <p>note <em>b</em> [c]</p>\n</div>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_unclosed_inline_footnote() {
+        let html = render_markdown("a^[note\n\nb]", &RenderOptions::default()).html;
+        assert_eq!(html, "<p>a^[note</p>\n<p>b]</p>\n");
+    }
+
+    #[test]
+    fn test_render_wikilink_label_markers() {
+        let html = render_markdown("[[Page|~~old~~ ==new==]]", &RenderOptions::default()).html;
+        assert_eq!(html, "<p>[[Page|~~old~~ ==new==]]</p>\n");
+    }
+
+    #[test]
+    fn test_render_label() {
+        assert_eq!(
+            render_label("~~old~~ ==new== <b>"),
+            "<del>old</del> <mark>new</mark> &lt;b&gt;"
+        );
+        assert_eq!(render_label("a == b"), "a == b");
+    }
+}
@@ -0,0 +1,939 @@
+use pulldown_cmark::{escape::escape_html, CodeBlockKind, Event, Tag};
+use std::{cmp::Ordering, fmt::Display};
+use thiserror::Error;
+
+use crate::{metadata::MetadataValue, note::Note, render::LinkResolver, vault::ItemPath};
+
+/// Languages of the code blocks evaluated as queries.
+const QUERY_LANGUAGES: &[&str] = &["dataview", "query"];
+
+/// A query written in a subset of the Dataview query language:
+///
+/// ```text
+/// LIST [expression] | TABLE [WITHOUT ID] expression [AS "Header"], ...
+/// FROM #tag | "folder" [and | or ...]
+/// WHERE expression
+/// SORT expression [ASC | DESC], ...
+/// LIMIT count
+/// ```
+#[derive(Debug, PartialEq)]
+pub(crate) struct Query {
+    kind: QueryKind,
+    from: Option<Source>,
+    filter: Option<Expr>,
+    sort: Vec<(Expr, bool)>,
+    limit: Option<usize>,
+}
+
+#[derive(Debug, PartialEq)]
+enum QueryKind {
+    List(Option<Expr>),
+    Table {
+        without_id: bool,
+        columns: Vec<(Expr, String)>,
+    },
+}
+
+/// Notes a query selects from.
+#[derive(Debug, PartialEq)]
+enum Source {
+    Tag(String),
+    Folder(String),
+    Not(Box<Source>),
+    And(Box<Source>, Box<Source>),
+    Or(Box<Source>, Box<Source>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Expr {
+    /// A frontmatter field, or a `file.` field, such as `file.name`.
+    Field(String),
+    Literal(Value),
+    Not(Box<Expr>),
+    Binary(Box<Expr>, BinaryOp, Box<Expr>),
+    Call(String, Vec<Expr>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BinaryOp {
+    And,
+    Or,
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+}
+
+/// Value of an expression for a note.
+#[derive(Debug, Clone, PartialEq)]
+pub(crate) enum Value {
+    Null,
+    Bool(bool),
+    Number(f64),
+    String(String),
+    List(Vec<Value>),
+    Link(ItemPath),
+}
+
+/// Notes a query found, with the values of its columns.
+#[derive(Debug, PartialEq)]
+pub(crate) struct QueryResult {
+    /// Headers of the columns of a table, `None` for lists.
+    headers: Option<Vec<String>>,
+    without_id: bool,
+    rows: Vec<(ItemPath, Vec<Value>)>,
+}
+
+impl Query {
+    pub fn parse(source: &str) -> Result<Query, QueryError> {
+        let tokens = tokenize(source)?;
+        QueryParser {
+            tokens,
+            position: 0,
+        }
+        .query()
+    }
+
+    /// Run the query against `notes`, by default ordered by path.
+    pub fn run<'a>(
+        &self,
+        notes: impl IntoIterator<Item = (&'a ItemPath, &'a Note)>,
+    ) -> QueryResult {
+        let mut notes: Vec<(&ItemPath, &Note)> = notes
+            .into_iter()
+            .filter(|(path, note)| match &self.from {
+                Some(source) => source.matches(path, note),
+                None => true,
+            })
+            .filter(|(path, note)| match &self.filter {
+                Some(filter) => filter.eval(path, note).is_truthy(),
+                None => true,
+            })
+            .collect();
+
+        notes.sort_by(|(path_a, note_a), (path_b, note_b)| {
+            self.sort
+                .iter()
+                .map(|(expr, descending)| {
+                    let ordering = expr
+                        .eval(path_a, note_a)
+                        .sort_cmp(&expr.eval(path_b, note_b));
+                    if *descending {
+                        ordering.reverse()
+                    } else {
+                        ordering
+                    }
+                })
+                .find(|ordering| ordering.is_ne())
+                .unwrap_or_else(|| path_a.cmp(path_b))
+        });
+
+        if let Some(limit) = self.limit {
+            notes.truncate(limit);
+        }
+
+        let (headers, without_id, columns): (_, _, Vec<&Expr>) = match &self.kind {
+            QueryKind::List(expr) => (None, false, expr.iter().collect()),
+            QueryKind::Table {
+                without_id,
+                columns,
+            } => (
+                Some(columns.iter().map(|(_, header)| header.clone()).collect()),
+                *without_id,
+                columns.iter().map(|(expr, _)| expr).collect(),
+            ),
+        };
+
+        let rows = notes
+            .into_iter()
+            .map(|(path, note)| {
+                let values = columns.iter().map(|expr| expr.eval(path, note)).collect();
+                (path.clone(), values)
+            })
+            .collect();
+
+        QueryResult {
+            headers,
+            without_id,
+            rows,
+        }
+    }
+}
+
+impl QueryResult {
+    /// HTML list or table of the results, with notes linked to `href`.
+    pub fn to_html(&self, href: impl Fn(&ItemPath) -> String) -> String {
+        let link = |path: &ItemPath| {
+            let title = path.file_name().unwrap_or_default();
+            format!(
+                "<a href=\"{}\" class=\"internal-link\">{}</a>",
+                escape(&href(path)),
+                escape(title)
+            )
+        };
+        let value_html = |value: &Value| value.to_html(&link);
+
+        let mut html = String::new();
+        match &self.headers {
+            None => {
+                html.push_str("<ul class=\"query-list\">\n");
+                for (path, values) in self.rows.iter() {
+                    html.push_str("<li>");
+                    html.push_str(&link(path));
+                    if let Some(value) = values.first().filter(|value| !value.to_text().is_empty())
+                    {
+                        html.push_str(": ");
+                        html.push_str(&value_html(value));
+                    }
+                    html.push_str("</li>\n");
+                }
+                html.push_str("</ul>\n");
+            }
+            Some(headers) => {
+                html.push_str("<table class=\"query-table\">\n<thead><tr>");
+                if !self.without_id {
+                    html.push_str(&format!("<th>File ({})</th>", self.rows.len()));
+                }
+                for header in headers {
+                    html.push_str(&format!("<th>{}</th>", escape(header)));
+                }
+                html.push_str("</tr></thead>\n<tbody>\n");
+                for (path, values) in self.rows.iter() {
+                    html.push_str("<tr>");
+                    if !self.without_id {
+                        html.push_str(&format!("<td>{}</td>", link(path)));
+                    }
+                    for value in values {
+                        html.push_str(&format!("<td>{}</td>", value_html(value)));
+                    }
+                    html.push_str("</tr>\n");
+                }
+                html.push_str("</tbody>\n</table>\n");
+            }
+        }
+
+        html
+    }
+}
+
+impl Source {
+    fn matches(&self, path: &ItemPath, note: &Note) -> bool {
+        match self {
+            Source::Tag(tag) => note.tags.iter().any(|note_tag| {
+                note_tag == tag
+                    || note_tag
+                        .strip_prefix(tag.as_str())
+                        .is_some_and(|rest| rest.starts_with('/'))
+            }),
+            Source::Folder(folder) => {
+                let folder = folder.trim_matches('/');
+                let folder = folder.strip_suffix(".md").unwrap_or(folder);
+                let path = path.to_string();
+                folder.is_empty()
+                    || path == folder
+                    || path
+                        .strip_prefix(folder)
+                        .is_some_and(|rest| rest.starts_with('/'))
+            }
+            Source::Not(source) => !source.matches(path, note),
+            Source::And(a, b) => a.matches(path, note) && b.matches(path, note),
+            Source::Or(a, b) => a.matches(path, note) || b.matches(path, note),
+        }
+    }
+}
+
+impl Expr {
+    fn eval(&self, path: &ItemPath, note: &Note) -> Value {
+        match self {
+            Expr::Field(name) => field(name, path, note),
+            Expr::Literal(value) => value.clone(),
+            Expr::Not(expr) => Value::Bool(!expr.eval(path, note).is_truthy()),
+            Expr::Binary(a, BinaryOp::And, b) => {
+                Value::Bool(a.eval(path, note).is_truthy() && b.eval(path, note).is_truthy())
+            }
+            Expr::Binary(a, BinaryOp::Or, b) => {
+                Value::Bool(a.eval(path, note).is_truthy() || b.eval(path, note).is_truthy())
+            }
+            Expr::Binary(a, op, b) => {
+                let (a, b) = (a.eval(path, note), b.eval(path, note));
+                let ordering = a.partial_cmp(&b);
+                Value::Bool(match op {
+                    BinaryOp::Eq => ordering == Some(Ordering::Equal),
+                    BinaryOp::Ne => ordering != Some(Ordering::Equal),
+                    BinaryOp::Lt => ordering == Some(Ordering::Less),
+                    BinaryOp::Le => matches!(ordering, Some(Ordering::Less | Ordering::Equal)),
+                    BinaryOp::Gt => ordering == Some(Ordering::Greater),
+                    BinaryOp::Ge => {
+                        matches!(ordering, Some(Ordering::Greater | Ordering::Equal))
+                    }
+                    BinaryOp::And | BinaryOp::Or => unreachable!(),
+                })
+            }
+            Expr::Call(function, args) => {
+                let args: Vec<Value> = args.iter().map(|arg| arg.eval(path, note)).collect();
+                call(function, &args)
+            }
+        }
+    }
+}
+
+impl Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Expr::Field(name) => f.write_str(name),
+            Expr::Literal(Value::String(value)) => write!(f, "\"{value}\""),
+            Expr::Literal(value) => write!(f, "{}", value.to_text()),
+            Expr::Not(expr) => write!(f, "!{expr}"),
+            Expr::Binary(a, op, b) => {
+                let op = match op {
+                    BinaryOp::And => "and",
+                    BinaryOp::Or => "or",
+                    BinaryOp::Eq => "=",
+                    BinaryOp::Ne => "!=",
+                    BinaryOp::Lt => "<",
+                    BinaryOp::Le => "<=",
+                    BinaryOp::Gt => ">",
+                    BinaryOp::Ge => ">=",
+                };
+                write!(f, "{a} {op} {b}")
+            }
+            Expr::Call(function, args) => {
+                let args: Vec<String> = args.iter().map(|arg| arg.to_string()).collect();
+                write!(f, "{function}({})", args.join(", "))
+            }
+        }
+    }
+}
+
+/// Value of a field of a note: `file.name`, `file.path`, `file.folder`,
+/// `file.link` and `file.tags`, or a frontmatter field, where `a.b` is the
+/// field `b` of the mapping `a`.
+fn field(name: &str, path: &ItemPath, note: &Note) -> Value {
+    let path_string = path.to_string();
+    let folder = path_string
+        .rsplit_once('/')
+        .map(|(folder, _)| folder)
+        .unwrap_or("");
+    match name {
+        "file.name" => Value::String(note.title.clone()),
+        "file.path" => Value::String(format!("{path_string}.md")),
+        "file.folder" => Value::String(folder.to_string()),
+        "file.link" => Value::Link(path.clone()),
+        "file.tags" => Value::List(
+            note.tags
+                .iter()
+                .map(|tag| Value::String(format!("#{tag}")))
+                .collect(),
+        ),
+        _ => note
+            .metadata
+            .get_path(name)
+            .map(Value::from)
+            .unwrap_or(Value::Null),
+    }
+}
+
+fn call(function: &str, args: &[Value]) -> Value {
+    match (function.to_lowercase().as_str(), args) {
+        ("contains", [Value::List(values), value]) => Value::Bool(values.contains(value)),
+        ("contains", [Value::String(text), Value::String(part)]) => {
+            Value::Bool(text.contains(part.as_str()))
+        }
+        ("length", [Value::List(values)]) => Value::Number(values.len() as f64),
+        ("length", [Value::String(text)]) => Value::Number(text.chars().count() as f64),
+        ("lower", [Value::String(text)]) => Value::String(text.to_lowercase()),
+        ("upper", [Value::String(text)]) => Value::String(text.to_uppercase()),
+        _ => Value::Null,
+    }
+}
+
+impl From<&MetadataValue> for Value {
+    fn from(value: &MetadataValue) -> Self {
+        match value {
+            MetadataValue::Boolean(value) => Value::Bool(*value),
+            MetadataValue::Integer(value) => Value::Number(*value as f64),
+            MetadataValue::Float(value) => Value::Number(*value),
+            MetadataValue::Tagged { value, .. } => Value::from(value.as_ref()),
+            // ISO dates sort and compare as text
+            MetadataValue::Date(value) => Value::String(value.to_string()),
+            MetadataValue::String(value) => Value::String(value.clone()),
+            MetadataValue::List(values) => Value::List(values.iter().map(Value::from).collect()),
+            MetadataValue::Map(_) | MetadataValue::Null => Value::Null,
+        }
+    }
+}
+
+impl Value {
+    fn is_truthy(&self) -> bool {
+        match self {
+            Value::Null => false,
+            Value::Bool(value) => *value,
+            Value::Number(value) => *value != 0.0,
+            Value::String(value) => !value.is_empty(),
+            Value::List(values) => !values.is_empty(),
+            Value::Link(_) => true,
+        }
+    }
+
+    /// Order of values for `SORT`, where values that can not be compared
+    /// are equal and missing ones go last.
+    fn sort_cmp(&self, other: &Value) -> Ordering {
+        match (self, other) {
+            (Value::Null, Value::Null) => Ordering::Equal,
+            (Value::Null, _) => Ordering::Greater,
+            (_, Value::Null) => Ordering::Less,
+            _ => self.partial_cmp(other).unwrap_or(Ordering::Equal),
+        }
+    }
+
+    fn to_text(&self) -> String {
+        match self {
+            Value::Null => String::new(),
+            Value::Bool(value) => value.to_string(),
+            Value::Number(value) => value.to_string(),
+            Value::String(value) => value.clone(),
+            Value::List(values) => values
+                .iter()
+                .map(Value::to_text)
+                .collect::<Vec<_>>()
+                .join(", "),
+            Value::Link(path) => path.to_string(),
+        }
+    }
+
+    fn to_html(&self, link: &impl Fn(&ItemPath) -> String) -> String {
+        match self {
+            Value::Link(path) => link(path),
+            Value::List(values) => values
+                .iter()
+                .map(|value| value.to_html(link))
+                .collect::<Vec<_>>()
+                .join(", "),
+            value => escape(&value.to_text()),
+        }
+    }
+}
+
+impl PartialOrd for Value {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        match (self, other) {
+            (Value::Null, Value::Null) => Some(Ordering::Equal),
+            (Value::Bool(a), Value::Bool(b)) => a.partial_cmp(b),
+            (Value::Number(a), Value::Number(b)) => a.partial_cmp(b),
+            (Value::String(a), Value::String(b)) => a.partial_cmp(b),
+            (Value::Link(a), Value::Link(b)) => a.partial_cmp(b),
+            (Value::List(a), Value::List(b)) if a == b => Some(Ordering::Equal),
+            _ => None,
+        }
+    }
+}
+
+fn escape(text: &str) -> String {
+    let mut html = String::new();
+    escape_html(&mut html, text).unwrap();
+    html
+}
+
+/// Replace ```` ```dataview ```` and ```` ```query ```` code blocks with the
+/// HTML `links` writes for their results. Blocks it returns no HTML for are
+/// kept as code.
+pub(crate) fn render_queries<'a>(
+    events: Vec<Event<'a>>,
+    links: &dyn LinkResolver,
+) -> Vec<Event<'a>> {
+    let mut output = Vec::with_capacity(events.len());
+    let mut events = events.into_iter();
+
+    while let Some(event) = events.next() {
+        if !is_query_block(&event) {
+            output.push(event);
+            continue;
+        }
+
+        let mut block = vec![event];
+        let mut source = String::new();
+        for event in events.by_ref() {
+            if let Event::Text(text) = &event {
+                source.push_str(text);
+            }
+            let end = matches!(event, Event::End(Tag::CodeBlock(_)));
+            block.push(event);
+            if end {
+                break;
+            }
+        }
+
+        match links.query(&source) {
+            Some(html) => output.push(Event::Html(html.into())),
+            None => output.extend(block),
+        }
+    }
+
+    output
+}
+
+pub(crate) fn is_query_block(event: &Event) -> bool {
+    match event {
+        Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info))) => info
+            .split_whitespace()
+            .next()
+            .is_some_and(|language| QUERY_LANGUAGES.contains(&language)),
+        _ => false,
+    }
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum QueryError {
+    #[error("unexpected character {0:?}")]
+    UnexpectedCharacter(char),
+
+    #[error("unterminated string")]
+    UnterminatedString,
+
+    #[error("expected {expected}, found {found}")]
+    Expected { expected: String, found: String },
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    /// A keyword, field or function name.
+    Word(String),
+    String(String),
+    Number(f64),
+    Tag(String),
+    Symbol(&'static str),
+}
+
+impl Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Word(word) => f.write_str(word),
+            Token::String(value) => write!(f, "\"{value}\""),
+            Token::Number(value) => write!(f, "{value}"),
+            Token::Tag(tag) => write!(f, "#{tag}"),
+            Token::Symbol(symbol) => f.write_str(symbol),
+        }
+    }
+}
+
+const SYMBOLS: &[&str] = &["!=", "<=", ">=", "=", "<", ">", "!", "(", ")", ",", "-"];
+
+fn tokenize(source: &str) -> Result<Vec<Token>, QueryError> {
+    let mut tokens = Vec::new();
+    let mut chars = source.char_indices().peekable();
+
+    while let Some(&(start, c)) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+        } else if c == '"' {
+            chars.next();
+            let mut value = String::new();
+            loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => match chars.next() {
+                        Some((_, c)) => value.push(c),
+                        None => return Err(QueryError::UnterminatedString),
+                    },
+                    Some((_, c)) => value.push(c),
+                    None => return Err(QueryError::UnterminatedString),
+                }
+            }
+            tokens.push(Token::String(value));
+        } else if c == '#' {
+            chars.next();
+            let tag = take_while(source, &mut chars, is_tag_char);
+            tokens.push(Token::Tag(tag.to_string()));
+        } else if c.is_ascii_digit() {
+            let number = take_while(source, &mut chars, |c| c.is_ascii_digit() || c == '.');
+            let value = number
+                .parse()
+                .map_err(|_| QueryError::UnexpectedCharacter(c))?;
+            tokens.push(Token::Number(value));
+        } else if c.is_alphabetic() || c == '_' {
+            let word = take_while(source, &mut chars, |c| is_tag_char(c) || c == '.');
+            tokens.push(Token::Word(word.to_string()));
+        } else {
+            let symbol = SYMBOLS
+                .iter()
+                .find(|symbol| source[start..].starts_with(*symbol))
+                .ok_or(QueryError::UnexpectedCharacter(c))?;
+            for _ in 0..symbol.len() {
+                chars.next();
+            }
+            tokens.push(Token::Symbol(symbol));
+        }
+    }
+
+    Ok(tokens)
+}
+
+fn is_tag_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_' || c == '-' || c == '/'
+}
+
+fn take_while<'a>(
+    source: &'a str,
+    chars: &mut std::iter::Peekable<std::str::CharIndices>,
+    predicate: impl Fn(char) -> bool,
+) -> &'a str {
+    let start = chars.peek().map(|(i, _)| *i).unwrap_or(source.len());
+    while chars.next_if(|(_, c)| predicate(*c)).is_some() {}
+    let end = chars.peek().map(|(i, _)| *i).unwrap_or(source.len());
+    &source[start..end]
+}
+
+struct QueryParser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl QueryParser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn error<T>(&self, expected: &str) -> Result<T, QueryError> {
+        Err(QueryError::Expected {
+            expected: expected.to_string(),
+            found: self
+                .peek()
+                .map(|token| token.to_string())
+                .unwrap_or_else(|| "end of query".to_string()),
+        })
+    }
+
+    /// Whether the next token is the keyword `keyword`, consuming it if so.
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn symbol(&mut self, symbol: &str) -> bool {
+        match self.peek() {
+            Some(Token::Symbol(s)) if *s == symbol => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn at_clause(&self) -> bool {
+        match self.peek() {
+            None => true,
+            Some(Token::Word(word)) => ["from", "where", "sort", "limit"]
+                .iter()
+                .any(|keyword| word.eq_ignore_ascii_case(keyword)),
+            _ => false,
+        }
+    }
+
+    fn query(mut self) -> Result<Query, QueryError> {
+        let kind = if self.keyword("list") {
+            QueryKind::List(match self.at_clause() {
+                true => None,
+                false => Some(self.expr()?),
+            })
+        } else if self.keyword("table") {
+            let without_id = self.keyword("without");
+            if without_id && !self.keyword("id") {
+                return self.error("ID");
+            }
+            let mut columns = Vec::new();
+            while !self.at_clause() {
+                if !columns.is_empty() && !self.symbol(",") {
+                    return self.error("`,`");
+                }
+                let expr = self.expr()?;
+                let header = if self.keyword("as") {
+                    match self.next() {
+                        Some(Token::String(header) | Token::Word(header)) => header,
+                        _ => {
+                            self.position -= 1;
+                            return self.error("a column name");
+                        }
+                    }
+                } else {
+                    expr.to_string()
+                };
+                columns.push((expr, header));
+            }
+            QueryKind::Table {
+                without_id,
+                columns,
+            }
+        } else {
+            return self.error("LIST or TABLE");
+        };
+
+        let mut query = Query {
+            kind,
+            from: None,
+            filter: None,
+            sort: Vec::new(),
+            limit: None,
+        };
+
+        while self.peek().is_some() {
+            if self.keyword("from") {
+                query.from = Some(self.source()?);
+            } else if self.keyword("where") {
+                query.filter = Some(self.expr()?);
+            } else if self.keyword("sort") {
+                loop {
+                    let expr = self.expr()?;
+                    let descending = self.keyword("desc");
+                    if !descending {
+                        self.keyword("asc");
+                    }
+                    query.sort.push((expr, descending));
+                    if !self.symbol(",") {
+                        break;
+                    }
+                }
+            } else if self.keyword("limit") {
+                match self.next() {
+                    Some(Token::Number(limit)) if limit >= 0.0 => {
+                        query.limit = Some(limit as usize)
+                    }
+                    _ => {
+                        self.position -= 1;
+                        return self.error("a number");
+                    }
+                }
+            } else {
+                return self.error("FROM, WHERE, SORT or LIMIT");
+            }
+        }
+
+        Ok(query)
+    }
+
+    fn source(&mut self) -> Result<Source, QueryError> {
+        let mut source = self.source_and()?;
+        while self.keyword("or") {
+            source = Source::Or(Box::new(source), Box::new(self.source_and()?));
+        }
+        Ok(source)
+    }
+
+    fn source_and(&mut self) -> Result<Source, QueryError> {
+        let mut source = self.source_atom()?;
+        while self.keyword("and") {
+            source = Source::And(Box::new(source), Box::new(self.source_atom()?));
+        }
+        Ok(source)
+    }
+
+    fn source_atom(&mut self) -> Result<Source, QueryError> {
+        match self.next() {
+            Some(Token::Tag(tag)) => Ok(Source::Tag(tag)),
+            Some(Token::String(folder)) => Ok(Source::Folder(folder)),
+            Some(Token::Symbol("-" | "!")) => Ok(Source::Not(Box::new(self.source_atom()?))),
+            Some(Token::Symbol("(")) => {
+                let source = self.source()?;
+                if !self.symbol(")") {
+                    return self.error("`)`");
+                }
+                Ok(source)
+            }
+            _ => {
+                self.position -= 1;
+                self.error("a #tag or \"folder\"")
+            }
+        }
+    }
+
+    fn expr(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.and_expr()?;
+        while self.keyword("or") {
+            expr = Expr::Binary(Box::new(expr), BinaryOp::Or, Box::new(self.and_expr()?));
+        }
+        Ok(expr)
+    }
+
+    fn and_expr(&mut self) -> Result<Expr, QueryError> {
+        let mut expr = self.comparison()?;
+        while self.keyword("and") {
+            expr = Expr::Binary(Box::new(expr), BinaryOp::And, Box::new(self.comparison()?));
+        }
+        Ok(expr)
+    }
+
+    fn comparison(&mut self) -> Result<Expr, QueryError> {
+        let expr = self.unary()?;
+        let op = match self.peek() {
+            Some(Token::Symbol("=")) => BinaryOp::Eq,
+            Some(Token::Symbol("!=")) => BinaryOp::Ne,
+            Some(Token::Symbol("<")) => BinaryOp::Lt,
+            Some(Token::Symbol("<=")) => BinaryOp::Le,
+            Some(Token::Symbol(">")) => BinaryOp::Gt,
+            Some(Token::Symbol(">=")) => BinaryOp::Ge,
+            _ => return Ok(expr),
+        };
+        self.position += 1;
+        Ok(Expr::Binary(Box::new(expr), op, Box::new(self.unary()?)))
+    }
+
+    fn unary(&mut self) -> Result<Expr, QueryError> {
+        if self.symbol("!") {
+            return Ok(Expr::Not(Box::new(self.unary()?)));
+        }
+        if self.symbol("-") {
+            return match self.next() {
+                Some(Token::Number(value)) => Ok(Expr::Literal(Value::Number(-value))),
+                _ => {
+                    self.position -= 1;
+                    self.error("a number")
+                }
+            };
+        }
+
+        match self.next() {
+            Some(Token::String(value)) => Ok(Expr::Literal(Value::String(value))),
+            Some(Token::Number(value)) => Ok(Expr::Literal(Value::Number(value))),
+            Some(Token::Tag(tag)) => Ok(Expr::Literal(Value::String(format!("#{tag}")))),
+            Some(Token::Symbol("(")) => {
+                let expr = self.expr()?;
+                if !self.symbol(")") {
+                    return self.error("`)`");
+                }
+                Ok(expr)
+            }
+            Some(Token::Word(word)) => match word.to_lowercase().as_str() {
+                "true" => Ok(Expr::Literal(Value::Bool(true))),
+                "false" => Ok(Expr::Literal(Value::Bool(false))),
+                "null" => Ok(Expr::Literal(Value::Null)),
+                _ if self.symbol("(") => {
+                    let mut args = Vec::new();
+                    while !self.symbol(")") {
+                        if !args.is_empty() && !self.symbol(",") {
+                            return self.error("`,` or `)`");
+                        }
+                        args.push(self.expr()?);
+                    }
+                    Ok(Expr::Call(word, args))
+                }
+                _ => Ok(Expr::Field(word)),
+            },
+            _ => {
+                self.position -= 1;
+                self.error("a field or value")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Query, QueryError};
+    use crate::{note::Note, vault::ItemPath};
+
+    fn notes() -> Vec<(ItemPath, Note)> {
+        [
+            (
+                "projects/Alpha",
+                "---\nstatus: active\npriority: 2\ntags: [project]\n---\n",
+            ),
+            (
+                "projects/Beta",
+                "---\nstatus: done\npriority: 1\n---\n#project",
+            ),
+            (
+                "projects/archive/Gamma",
+                "---\npriority: 3\n---\n#project/old",
+            ),
+            ("Journal", "#daily"),
+        ]
+        .into_iter()
+        .map(|(path, content)| {
+            let title = path.rsplit('/').next().unwrap();
+            (
+                ItemPath::from_path(path),
+                Note::parse(title, content).unwrap(),
+            )
+        })
+        .collect()
+    }
+
+    fn run(query: &str) -> String {
+        let notes = notes();
+        Query::parse(query)
+            .unwrap()
+            .run(notes.iter().map(|(path, note)| (path, note)))
+            .to_html(|path| format!("/{path}.html"))
+    }
+
+    #[test]
+    fn test_list_query() {
+        assert_eq!(
+            run("LIST FROM #project WHERE priority > 1 SORT priority DESC"),
+            "<ul class=\"query-list\">\n\
+            <li><a href=\"/projects/archive/Gamma.html\" class=\"internal-link\">Gamma</a></li>\n\
+            <li><a href=\"/projects/Alpha.html\" class=\"internal-link\">Alpha</a></li>\n\
+            </ul>\n"
+        );
+    }
+
+    #[test]
+    fn test_table_query() {
+        assert_eq!(
+            run("table status as \"State\", priority\nfrom \"projects\" and -\"projects/archive\"\nsort file.name\nlimit 5"),
+            "<table class=\"query-table\">\n\
+            <thead><tr><th>File (2)</th><th>State</th><th>priority</th></tr></thead>\n\
+            <tbody>\n\
+            <tr><td><a href=\"/projects/Alpha.html\" class=\"internal-link\">Alpha</a></td><td>active</td><td>2</td></tr>\n\
+            <tr><td><a href=\"/projects/Beta.html\" class=\"internal-link\">Beta</a></td><td>done</td><td>1</td></tr>\n\
+            </tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn test_query_functions() {
+        assert_eq!(
+            run("LIST file.folder WHERE contains(file.tags, \"#daily\") or !priority"),
+            "<ul class=\"query-list\">\n\
+            <li><a href=\"/Journal.html\" class=\"internal-link\">Journal</a></li>\n\
+            </ul>\n"
+        );
+        assert_eq!(
+            run("TABLE WITHOUT ID file.link FROM #project LIMIT 1"),
+            "<table class=\"query-table\">\n<thead><tr><th>file.link</th></tr></thead>\n<tbody>\n\
+            <tr><td><a href=\"/projects/Alpha.html\" class=\"internal-link\">Alpha</a></td></tr>\n\
+            </tbody>\n</table>\n"
+        );
+    }
+
+    #[test]
+    fn test_query_errors() {
+        assert_eq!(
+            Query::parse("TASK FROM #a").unwrap_err(),
+            QueryError::Expected {
+                expected: "LIST or TABLE".to_string(),
+                found: "TASK".to_string()
+            }
+        );
+        assert_eq!(
+            Query::parse("LIST FROM").unwrap_err().to_string(),
+            "expected a #tag or \"folder\", found end of query"
+        );
+        assert_eq!(
+            Query::parse("LIST WHERE a = \"b").unwrap_err(),
+            QueryError::UnterminatedString
+        );
+    }
+}
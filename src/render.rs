@@ -0,0 +1,197 @@
+use pulldown_cmark::{escape::escape_html, Event, Tag};
+use std::collections::HashMap;
+
+use crate::{
+    callout::{render_callouts, CalloutType},
+    inline_field::{render_inline_fields, InlineFieldStyle},
+    mermaid::render_mermaid,
+    obsidian::{render_highlights, render_inline_footnotes, strip_comments},
+    query::render_queries,
+    wikilink::{parse_with_wikilinks_map, Wikilink},
+};
+
+/// Options that change how a note's markdown is rendered to HTML.
+#[derive(Debug, Clone, Default)]
+pub struct RenderOptions {
+    /// Custom callout types, by name.
+    pub callouts: HashMap<String, CalloutType>,
+
+    /// Command used to render mermaid diagrams to SVG at build time.
+    pub mermaid_command: Option<Vec<String>>,
+
+    /// How `key:: value` inline fields are written.
+    pub inline_fields: InlineFieldStyle,
+}
+
+/// HTML rendered from a note.
+#[derive(Debug, PartialEq)]
+pub struct RenderedHtml {
+    pub html: String,
+
+    /// The HTML contains mermaid diagrams to be rendered in the browser.
+    pub mermaid: bool,
+}
+
+/// Resolves the links of a note to the pages and files of a site while the
+/// note is rendered.
+pub trait LinkResolver {
+    /// Events written in place of a wikilink, or `None` to keep it as text.
+    fn wikilink(&self, link: &Wikilink) -> Option<Vec<Event<'static>>>;
+
+    /// URL written in place of the destination of a markdown link or image,
+    /// or `None` to keep it as written.
+    fn markdown_link(&self, dest: &str) -> Option<String>;
+
+    /// HTML written in place of a query block, or `None` to keep it as code.
+    fn query(&self, _source: &str) -> Option<String> {
+        None
+    }
+}
+
+/// Render markdown to HTML, applying the Obsidian-specific transformations
+/// to the event stream before it is written. Links are kept as written.
+pub fn render_markdown(content: &str, options: &RenderOptions) -> RenderedHtml {
+    render_markdown_with_links(content, options, None)
+}
+
+/// Render markdown to HTML, resolving its links with `links`. `%%comments%%`
+/// are left out, whichever page the markdown is part of.
+pub fn render_markdown_with_links(
+    content: &str,
+    options: &RenderOptions,
+    links: Option<&dyn LinkResolver>,
+) -> RenderedHtml {
+    let content = strip_comments(content);
+    let content = render_inline_fields(&content, options.inline_fields);
+    let events = parse_with_wikilinks_map(
+        &content,
+        |wikilink, text| {
+            links
+                .and_then(|links| links.wikilink(wikilink))
+                .unwrap_or_else(|| {
+                    // kept as written
+                    let mut html = String::new();
+                    escape_html(&mut html, text).unwrap();
+                    vec![Event::Html(html.into())]
+                })
+        },
+        // the URLs of wikilinks are already resolved
+        |event| match links {
+            Some(links) => resolve_markdown_link(event, links),
+            None => event,
+        },
+    );
+
+    let events = match links {
+        Some(links) => render_queries(events, links),
+        None => events,
+    };
+    let (events, mermaid) = render_mermaid(events, options.mermaid_command.as_deref());
+    let events = render_highlights(events);
+    let events = render_inline_footnotes(events);
+    let events = render_callouts(events, &options.callouts);
+
+    let mut html_buf = String::new();
+    pulldown_cmark::html::push_html(&mut html_buf, events.into_iter());
+
+    RenderedHtml {
+        html: html_buf,
+        mermaid,
+    }
+}
+
+/// Point a markdown link or image to the URL `links` resolves.
+fn resolve_markdown_link<'a>(event: Event<'a>, links: &dyn LinkResolver) -> Event<'a> {
+    match event {
+        Event::Start(Tag::Link(link_type, dest, title)) => {
+            let dest = links.markdown_link(&dest).map(Into::into).unwrap_or(dest);
+            Event::Start(Tag::Link(link_type, dest, title))
+        }
+        Event::Start(Tag::Image(link_type, dest, title)) => {
+            let dest = links.markdown_link(&dest).map(Into::into).unwrap_or(dest);
+            Event::Start(Tag::Image(link_type, dest, title))
+        }
+        event => event,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use pulldown_cmark::{CowStr, Event, LinkType, Tag};
+
+    use super::{render_markdown_with_links, LinkResolver, RenderOptions};
+    use crate::wikilink::Wikilink;
+
+    struct TestLinks;
+
+    impl LinkResolver for TestLinks {
+        fn wikilink(&self, link: &Wikilink) -> Option<Vec<Event<'static>>> {
+            if link.target == "Missing" {
+                return None;
+            }
+            let url = CowStr::from(format!("/{}.html", link.target));
+            let tag = Tag::Link(LinkType::Inline, url, "".into());
+            let label = link.label.clone().unwrap_or(link.target.clone());
+            Some(vec![
+                Event::Start(tag.clone()),
+                Event::Text(label.into()),
+                Event::End(tag),
+            ])
+        }
+
+        fn markdown_link(&self, dest: &str) -> Option<String> {
+            dest.strip_suffix(".md")
+                .or_else(|| dest.strip_suffix(".html"))
+                .map(|dest| format!("/{dest}.html"))
+        }
+    }
+
+    fn render(content: &str) -> String {
+        render_markdown_with_links(content, &RenderOptions::default(), Some(&TestLinks)).html
+    }
+
+    #[test]
+    fn test_render_wikilinks() {
+        assert_eq!(
+            render("see [[Page|a < b & c]] and [[Missing]]"),
+            "<p>see <a href=\"/Page.html\">a &lt; b &amp; c</a> and [[Missing]]</p>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_wikilinks_in_code() {
+        assert_eq!(
+            render("`[[Page]]`\n\n```\n[[Page]]\n```"),
+            "<p><code>[[Page]]</code></p>\n<pre><code>[[Page]]\n</code></pre>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_wikilinks_split_by_markdown() {
+        assert_eq!(
+            render("- [[Page|a &amp; *b*]] [[c\\_d]]"),
+            "<ul>\n<li><a href=\"/Page.html\">a &amp;amp; *b*</a> <a href=\"/c%5C_d.html\">c\\_d</a></li>\n</ul>\n"
+        );
+        assert_eq!(
+            render("| a |\n| - |\n| [[Page\\|b]] |"),
+            "<table><thead><tr><th>a</th></tr></thead><tbody>\n\
+            <tr><td><a href=\"/Page.html\">b</a></td></tr>\n</tbody></table>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_links() {
+        assert_eq!(
+            render("[a](Other.md) [b](https://example.com)"),
+            "<p><a href=\"/Other.html\">a</a> <a href=\"https://example.com\">b</a></p>\n"
+        );
+    }
+
+    #[test]
+    fn test_render_markdown_links_leave_wikilinks() {
+        assert_eq!(
+            render("[[Page]] [a](Page.html)"),
+            "<p><a href=\"/Page.html\">Page</a> <a href=\"/Page.html\">a</a></p>\n"
+        );
+    }
+}
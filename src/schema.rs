@@ -0,0 +1,426 @@
+use serde::Deserialize;
+use std::{collections::BTreeMap, fmt::Display};
+use thiserror::Error;
+
+use crate::{date::Date, metadata::MetadataValue, note::Note, vault::ItemPath};
+
+/// Largest edit distance of an unknown key to a field of the schema for it
+/// to be reported as a misspelling of the field. Keys shorter than
+/// `SHORT_KEY_LENGTH` can only be one edit away.
+const MAX_TYPO_DISTANCE: usize = 2;
+const SHORT_KEY_LENGTH: usize = 5;
+
+/// Keys of Obsidian and of the site itself, never taken for misspellings.
+const KNOWN_KEYS: &[&str] = &[
+    "tag",
+    "tags",
+    "alias",
+    "aliases",
+    "cssclasses",
+    "date",
+    "created",
+    "updated",
+    "modified",
+    "last_modified",
+    "menu_title",
+    "hide_from_menu",
+    "order",
+    "weight",
+];
+
+/// Fields the metadata of notes can have, from the `schema` of `site.yaml`.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct MetadataSchema {
+    /// Fields of every note, by key.
+    pub fields: BTreeMap<String, FieldSchema>,
+
+    /// Report every frontmatter key the schema does not have. Otherwise,
+    /// only the ones close to a field of the schema, such as `pubished` for
+    /// `published`, are reported.
+    pub strict: bool,
+
+    /// Fields of the notes of a folder or with a tag, added to `fields`.
+    pub rules: Vec<SchemaRule>,
+}
+
+/// Options of a field. The ones set by a rule replace the ones of the
+/// schema's `fields`.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct FieldSchema {
+    #[serde(rename = "type")]
+    pub kind: Option<FieldType>,
+    pub required: Option<bool>,
+
+    /// Values the field can have. Lists can only have these items.
+    pub values: Option<Vec<String>>,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+#[serde(rename_all = "lowercase")]
+pub enum FieldType {
+    Text,
+    Number,
+    Integer,
+    Boolean,
+    Date,
+    List,
+    Map,
+}
+
+/// Fields of the notes in `folder`, or with `tag`, or both when both are
+/// set.
+#[derive(Deserialize, Debug, Clone, Default, PartialEq)]
+#[serde(default)]
+pub struct SchemaRule {
+    pub folder: Option<String>,
+    pub tag: Option<String>,
+    pub fields: BTreeMap<String, FieldSchema>,
+}
+
+#[derive(Error, Debug, PartialEq)]
+pub enum SchemaError {
+    #[error("missing required field `{0}`")]
+    MissingField(String),
+
+    #[error("`{key}` is {found}, expected {expected}")]
+    WrongType {
+        key: String,
+        expected: FieldType,
+        found: &'static str,
+    },
+
+    #[error("`{key}` is `{value}`, expected one of {}", .allowed.join(", "))]
+    NotAllowed {
+        key: String,
+        value: String,
+        allowed: Vec<String>,
+    },
+
+    #[error("unknown field `{key}`{}", .suggestion.as_ref().map(|field| format!(", did you mean `{field}`?")).unwrap_or_default())]
+    UnknownField {
+        key: String,
+        suggestion: Option<String>,
+    },
+}
+
+impl MetadataSchema {
+    pub fn is_empty(&self) -> bool {
+        self.fields.is_empty() && self.rules.is_empty()
+    }
+
+    /// Problems of the metadata of the note at `path`, by key.
+    pub fn validate(&self, path: &ItemPath, note: &Note) -> Vec<SchemaError> {
+        let fields = self.fields_of(path, note);
+        let metadata = &note.metadata;
+        let mut errors = Vec::new();
+
+        for (key, field) in &fields {
+            match metadata.get(key).map(MetadataValue::untagged) {
+                None | Some(MetadataValue::Null) => {
+                    if field.required == Some(true) {
+                        errors.push(SchemaError::MissingField(key.clone()));
+                    }
+                }
+                Some(value) => errors.extend(field.validate(key, value)),
+            }
+        }
+
+        let mut unknown: Vec<&str> = metadata
+            .keys()
+            .filter(|key| !fields.contains_key(*key) && !metadata.is_inline(key))
+            .collect();
+        unknown.sort();
+        for key in unknown {
+            let suggestion =
+                closest_field(key, fields.keys()).filter(|_| !KNOWN_KEYS.contains(&key));
+            if self.strict || suggestion.is_some() {
+                errors.push(SchemaError::UnknownField {
+                    key: key.to_string(),
+                    suggestion,
+                });
+            }
+        }
+
+        errors
+    }
+
+    /// Fields of the note at `path`, with the ones of the rules it matches.
+    fn fields_of(&self, path: &ItemPath, note: &Note) -> BTreeMap<String, FieldSchema> {
+        let mut fields = self.fields.clone();
+        for rule in self.rules.iter().filter(|rule| rule.matches(path, note)) {
+            for (key, field) in &rule.fields {
+                let merged = fields.entry(key.clone()).or_default();
+                merged.kind = field.kind.or(merged.kind);
+                merged.required = field.required.or(merged.required);
+                if field.values.is_some() {
+                    merged.values = field.values.clone();
+                }
+            }
+        }
+        fields
+    }
+}
+
+impl SchemaRule {
+    fn matches(&self, path: &ItemPath, note: &Note) -> bool {
+        let in_folder = self.folder.as_ref().is_none_or(|folder| {
+            let folder: Vec<&str> = folder.split('/').filter(|c| !c.is_empty()).collect();
+            match path {
+                ItemPath::Absolute(components) => {
+                    components.len() > folder.len()
+                        && components.iter().zip(&folder).all(|(a, b)| a == b)
+                }
+                _ => false,
+            }
+        });
+        let tagged = self.tag.as_ref().is_none_or(|tag| {
+            let tag = tag.trim_start_matches('#');
+            note.tags
+                .iter()
+                .any(|t| t == tag || t.starts_with(&format!("{tag}/")))
+        });
+
+        in_folder && tagged
+    }
+}
+
+impl FieldSchema {
+    fn validate(&self, key: &str, value: &MetadataValue) -> Vec<SchemaError> {
+        if let Some(expected) = self.kind {
+            if !expected.matches(value) {
+                return vec![SchemaError::WrongType {
+                    key: key.to_string(),
+                    expected,
+                    found: type_name(value),
+                }];
+            }
+        }
+
+        let Some(allowed) = &self.values else {
+            return Vec::new();
+        };
+        let items = match value {
+            MetadataValue::List(items) => items.iter().collect(),
+            value => vec![value],
+        };
+        items
+            .into_iter()
+            .filter_map(|item| {
+                let text = value_text(item.untagged())?;
+                (!allowed.contains(&text)).then(|| SchemaError::NotAllowed {
+                    key: key.to_string(),
+                    value: text,
+                    allowed: allowed.clone(),
+                })
+            })
+            .collect()
+    }
+}
+
+impl FieldType {
+    fn matches(&self, value: &MetadataValue) -> bool {
+        match (self, value) {
+            // quoted dates, and dates in JSON, are text written as a date
+            (FieldType::Date, MetadataValue::String(text)) => text.parse::<Date>().is_ok(),
+            _ => matches!(
+                (self, value),
+                (FieldType::Text, MetadataValue::String(_))
+                    | (
+                        FieldType::Number,
+                        MetadataValue::Integer(_) | MetadataValue::Float(_)
+                    )
+                    | (FieldType::Integer, MetadataValue::Integer(_))
+                    | (FieldType::Boolean, MetadataValue::Boolean(_))
+                    | (FieldType::Date, MetadataValue::Date(_))
+                    | (FieldType::List, MetadataValue::List(_))
+                    | (FieldType::Map, MetadataValue::Map(_))
+            ),
+        }
+    }
+}
+
+impl Display for FieldType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            FieldType::Text => "text",
+            FieldType::Number => "a number",
+            FieldType::Integer => "an integer",
+            FieldType::Boolean => "a boolean",
+            FieldType::Date => "a date",
+            FieldType::List => "a list",
+            FieldType::Map => "a map",
+        };
+        f.write_str(name)
+    }
+}
+
+fn type_name(value: &MetadataValue) -> &'static str {
+    match value.untagged() {
+        MetadataValue::Boolean(_) => "a boolean",
+        MetadataValue::Date(_) => "a date",
+        MetadataValue::Float(_) => "a number",
+        MetadataValue::Integer(_) => "an integer",
+        MetadataValue::List(_) => "a list",
+        MetadataValue::Map(_) => "a map",
+        MetadataValue::Null => "empty",
+        MetadataValue::String(_) => "text",
+        MetadataValue::Tagged { .. } => "tagged",
+    }
+}
+
+/// Value as written, for the values of `values`.
+fn value_text(value: &MetadataValue) -> Option<String> {
+    match value {
+        MetadataValue::Boolean(value) => Some(value.to_string()),
+        MetadataValue::Date(value) => Some(value.to_string()),
+        MetadataValue::Float(value) => Some(value.to_string()),
+        MetadataValue::Integer(value) => Some(value.to_string()),
+        MetadataValue::String(value) => Some(value.clone()),
+        _ => None,
+    }
+}
+
+/// Field of the schema `key` is most likely a misspelling of, if any.
+fn closest_field<'a>(key: &str, fields: impl Iterator<Item = &'a String>) -> Option<String> {
+    fields
+        .map(|field| {
+            (
+                edit_distance(&key.to_lowercase(), &field.to_lowercase()),
+                field,
+            )
+        })
+        .filter(|(distance, _)| {
+            let max_distance = if key.chars().count() < SHORT_KEY_LENGTH {
+                1
+            } else {
+                MAX_TYPO_DISTANCE
+            };
+            *distance <= max_distance
+        })
+        .min_by_key(|(distance, _)| *distance)
+        .map(|(_, field)| field.clone())
+}
+
+/// Levenshtein distance between `a` and `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    for (i, a) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, b) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a != *b);
+            current.push(substitution.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{MetadataSchema, SchemaError};
+    use crate::{note::Note, vault::ItemPath};
+
+    fn schema() -> MetadataSchema {
+        serde_yaml::from_str(
+            r#"
+fields:
+  title: {type: text, required: true}
+  published: {type: boolean}
+  status: {values: [draft, done]}
+  tags: {type: list}
+rules:
+  - folder: blog
+    fields:
+      date: {type: date, required: true}
+  - tag: project
+    fields:
+      status: {required: true, values: [active, done]}
+"#,
+        )
+        .unwrap()
+    }
+
+    fn validate(schema: &MetadataSchema, path: &str, content: &str) -> Vec<SchemaError> {
+        let note = Note::parse("Note", content).unwrap();
+        schema.validate(&ItemPath::from_path(path), &note)
+    }
+
+    #[test]
+    fn test_schema_fields() {
+        let schema = schema();
+        assert_eq!(
+            validate(&schema, "note", "---\ntitle: Note\npublished: true\n---\n"),
+            vec![]
+        );
+        assert_eq!(
+            validate(&schema, "note", "---\npublished: yes\nstatus: wip\n---\n"),
+            vec![
+                SchemaError::WrongType {
+                    key: "published".to_string(),
+                    expected: super::FieldType::Boolean,
+                    found: "text"
+                },
+                SchemaError::NotAllowed {
+                    key: "status".to_string(),
+                    value: "wip".to_string(),
+                    allowed: vec!["draft".to_string(), "done".to_string()]
+                },
+                SchemaError::MissingField("title".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_schema_unknown_fields() {
+        let mut schema = schema();
+        let content = "---\ntitle: Note\npubished: true\nextra: 1\n---\nkey:: value";
+        let errors = validate(&schema, "note", content);
+        assert_eq!(
+            errors,
+            vec![SchemaError::UnknownField {
+                key: "pubished".to_string(),
+                suggestion: Some("published".to_string())
+            }]
+        );
+        assert_eq!(
+            errors[0].to_string(),
+            "unknown field `pubished`, did you mean `published`?"
+        );
+
+        schema.strict = true;
+        assert_eq!(validate(&schema, "note", content).len(), 2);
+    }
+
+    #[test]
+    fn test_schema_rules() {
+        let schema = schema();
+        assert_eq!(
+            validate(&schema, "blog/post", "---\ntitle: Post\n---\n"),
+            vec![SchemaError::MissingField("date".to_string())]
+        );
+        assert_eq!(validate(&schema, "blog", "---\ntitle: Blog\n---\n"), vec![]);
+        assert_eq!(
+            validate(
+                &schema,
+                "blog/post",
+                "---\ntitle: \"2024-03-01\"\ndate: \"2024-03-01\"\n---\n"
+            ),
+            vec![]
+        );
+        assert_eq!(
+            validate(
+                &schema,
+                "work/x",
+                "---\ntitle: X\nstatus: draft\n---\n#project/x"
+            ),
+            vec![SchemaError::NotAllowed {
+                key: "status".to_string(),
+                value: "draft".to_string(),
+                allowed: vec!["active".to_string(), "done".to_string()]
+            }]
+        );
+    }
+}
@@ -0,0 +1,219 @@
+use pulldown_cmark::{Event, Tag};
+use serde::Serialize;
+use std::{
+    collections::{BTreeMap, HashMap},
+    fs::File,
+    io::BufWriter,
+    path::Path,
+};
+
+use crate::{note::Note, query::is_query_block, vault::ItemPath};
+
+const TITLE_WEIGHT: u32 = 10;
+const HEADING_WEIGHT: u32 = 5;
+const TAG_WEIGHT: u32 = 5;
+const TEXT_WEIGHT: u32 = 1;
+
+const MIN_TERM_LENGTH: usize = 2;
+const EXCERPT_LENGTH: usize = 160;
+
+/// `[document, score]` pairs for a term.
+type Postings = Vec<(usize, u32)>;
+
+/// Full-text search index written next to the generated pages.
+///
+/// The index is split into `documents.json`, holding what the client needs to
+/// display a result, and one `terms/<shard>.json` file per first character of
+/// the indexed terms, so a query only downloads the shards it touches.
+#[derive(Default)]
+pub(crate) struct SearchIndex {
+    documents: Vec<SearchDocument>,
+    terms: HashMap<String, BTreeMap<usize, u32>>,
+}
+
+#[derive(Serialize, Debug, PartialEq)]
+struct SearchDocument {
+    path: String,
+    title: String,
+    tags: Vec<String>,
+    headings: Vec<String>,
+    excerpt: String,
+}
+
+impl SearchIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_note(&mut self, path: &ItemPath, note: &Note) {
+        let doc = self.documents.len();
+        let (text, headings) = extract_text(&note.content);
+
+        self.add_terms(doc, &note.title, TITLE_WEIGHT);
+        for heading in headings.iter() {
+            self.add_terms(doc, heading, HEADING_WEIGHT);
+        }
+        for tag in note.tags.iter() {
+            self.add_terms(doc, tag, TAG_WEIGHT);
+        }
+        self.add_terms(doc, &text, TEXT_WEIGHT);
+
+        self.documents.push(SearchDocument {
+            path: path.to_string(),
+            title: note.title.clone(),
+            tags: note.tags.clone(),
+            headings,
+            excerpt: excerpt(&text),
+        });
+    }
+
+    fn add_terms(&mut self, doc: usize, text: &str, weight: u32) {
+        for term in tokenize(text) {
+            *self.terms.entry(term).or_default().entry(doc).or_insert(0) += weight;
+        }
+    }
+
+    /// Group terms by shard, each term mapping to `[document, score]` pairs
+    /// sorted by descending score.
+    fn shards(&self) -> BTreeMap<String, BTreeMap<&str, Postings>> {
+        let mut shards: BTreeMap<String, BTreeMap<&str, Postings>> = BTreeMap::new();
+
+        for (term, postings) in self.terms.iter() {
+            let mut postings: Postings =
+                postings.iter().map(|(doc, score)| (*doc, *score)).collect();
+            postings.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+
+            shards
+                .entry(shard_key(term))
+                .or_default()
+                .insert(term, postings);
+        }
+
+        shards
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, directory: P) -> Result<(), std::io::Error> {
+        let terms_directory = directory.as_ref().join("terms");
+        std::fs::create_dir_all(&terms_directory)?;
+
+        let file = File::create(directory.as_ref().join("documents.json"))?;
+        serde_json::to_writer(BufWriter::new(file), &self.documents)?;
+
+        for (key, terms) in self.shards() {
+            let file = File::create(terms_directory.join(format!("{key}.json")))?;
+            serde_json::to_writer(BufWriter::new(file), &terms)?;
+        }
+
+        Ok(())
+    }
+}
+
+/// Split `text`, lowercased first, into terms of alphabetic and numeric
+/// characters, as `/[^\p{Alphabetic}\p{N}]+/u` does.
+///
+/// Must be kept in sync with the tokenizer in `search_form.html`.
+pub(crate) fn tokenize(text: &str) -> impl Iterator<Item = String> {
+    let text = text.to_lowercase();
+    let terms: Vec<String> = text
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|term| term.chars().count() >= MIN_TERM_LENGTH)
+        .map(str::to_string)
+        .collect();
+    terms.into_iter()
+}
+
+/// Shard name for a term: the hex code point of its first character.
+fn shard_key(term: &str) -> String {
+    let first = term.chars().next().unwrap_or('_');
+    format!("{:x}", first as u32)
+}
+
+/// Extract the plain text and the headings of a markdown document. The
+/// source of queries is left out.
+fn extract_text(content: &str) -> (String, Vec<String>) {
+    let parser = pulldown_cmark::Parser::new_ext(content, pulldown_cmark::Options::all());
+
+    let mut text = String::new();
+    let mut headings = Vec::new();
+    let mut heading: Option<String> = None;
+    let mut in_query = false;
+
+    for event in parser {
+        match event {
+            ref event if is_query_block(event) => in_query = true,
+            Event::End(Tag::CodeBlock(_)) if in_query => in_query = false,
+            _ if in_query => {}
+            Event::Start(Tag::Heading(..)) => heading = Some(String::new()),
+            Event::End(Tag::Heading(..)) => {
+                if let Some(heading) = heading.take() {
+                    headings.push(heading);
+                }
+            }
+            Event::Text(value) | Event::Code(value) => match heading.as_mut() {
+                Some(heading) => heading.push_str(&value),
+                None => text.push_str(&value),
+            },
+            Event::SoftBreak | Event::HardBreak | Event::End(Tag::Paragraph) => text.push(' '),
+            _ => {}
+        }
+    }
+
+    (text, headings)
+}
+
+fn excerpt(text: &str) -> String {
+    let text = text.split_whitespace().collect::<Vec<&str>>().join(" ");
+    match text.char_indices().nth(EXCERPT_LENGTH) {
+        Some((pos, _)) => format!("{}…", &text[..pos]),
+        None => text,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{extract_text, shard_key, tokenize, SearchIndex};
+    use crate::{note::Note, vault::ItemPath};
+
+    #[test]
+    fn test_tokenize() {
+        let terms: Vec<String> = tokenize("Hello, World! a b2 Ação_x").collect();
+        assert_eq!(terms, vec!["hello", "world", "b2", "ação"]);
+
+        // lowercased before splitting, as in the browser: `İ` is `i` and a
+        // combining dot, which is not alphabetic
+        let terms: Vec<String> = tokenize("İstanbul STRAẞE Ωμέγα ΟΔΟΣ 東京").collect();
+        assert_eq!(terms, vec!["stanbul", "straße", "ωμέγα", "οδος", "東京"]);
+    }
+
+    #[test]
+    fn test_shard_key() {
+        assert_eq!(shard_key("obsidian"), "6f");
+        assert_eq!(shard_key("ação"), "61");
+        assert_eq!(shard_key("éter"), "e9");
+    }
+
+    #[test]
+    fn test_extract_text() {
+        let (text, headings) = extract_text("# Title\n\nSome `code` here.\n\n## Sub");
+        assert_eq!(text.trim(), "Some code here.");
+        assert_eq!(headings, vec!["Title".to_string(), "Sub".to_string()]);
+
+        let (text, _) = extract_text("Before\n\n```dataview\nLIST FROM #project\n```\n\nAfter");
+        assert_eq!(
+            text.split_whitespace().collect::<Vec<_>>(),
+            vec!["Before", "After"]
+        );
+    }
+
+    #[test]
+    fn test_search_index_scores() {
+        let mut index = SearchIndex::new();
+        let note = Note::parse("Garden", "# Plants\n\nA garden of plants. #botany").unwrap();
+        index.add_note(&ItemPath::from("notes/Garden".to_string()), &note);
+
+        let shards = index.shards();
+        assert_eq!(shards["67"]["garden"], vec![(0, 11)]);
+        assert_eq!(shards["70"]["plants"], vec![(0, 6)]);
+        assert_eq!(shards["62"]["botany"], vec![(0, 6)]);
+    }
+}
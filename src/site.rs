@@ -1,14 +1,49 @@
 use std::{
-    collections::HashMap,
+    cell::RefCell,
+    collections::{BTreeSet, HashSet},
     fs::File,
     path::{Path, PathBuf},
 };
 
 use minijinja::{context, path_loader, Environment};
+use petgraph::stable_graph::StableGraph;
+use pulldown_cmark::{
+    escape::{escape_href, escape_html},
+    Event, LinkType, Tag,
+};
 use serde::Serialize;
 use thiserror::Error;
 
-use crate::vault::{EmbeddedFile, ItemPath, Vault};
+use crate::{
+    attachment::{EmbedKind, FileType, FileTypes},
+    canvas::{default_node_html, render_board, Canvas, CanvasNodeKind},
+    config::SiteConfig,
+    date::{add_date_filters, Date},
+    feed::{atom_feed, FeedAuthor, FeedEntry, FEED_FILE},
+    images::ResponsiveImage,
+    manifest::Manifest,
+    menu::{Menu, MenuPage},
+    metadata::{MetadataError, PUBLISHED_FIELDS, UPDATED_FIELDS},
+    note::Note,
+    obsidian::render_label,
+    query::Query,
+    render::{render_markdown_with_links, LinkResolver, RenderOptions},
+    search::SearchIndex,
+    vault::{EmbeddedFile, ItemPath, Vault},
+    wikilink::{EmbedOptions, Wikilink},
+};
+
+/// Note shown above the listing of the folder it is in.
+const FOLDER_INTRO_NOTE: &str = "folder";
+
+/// Note shown above the listing of the folder it is in, as its
+/// `index.html`, instead of a page of its own. Preferred to `folder.md`.
+const FOLDER_INDEX_NOTE: &str = "index";
+
+/// Mermaid runtime vendored in the `_static` directory of templates by
+/// `scripts/vendor-mermaid.sh`: the `dist` directory of the `mermaid`
+/// package, with its chunks.
+const VENDORED_MERMAID_RUNTIME: &str = "_static/mermaid/mermaid.esm.min.mjs";
 
 pub(crate) struct Site<'a> {
     vault: &'a Vault,
@@ -17,38 +52,90 @@ pub(crate) struct Site<'a> {
     base_url: String,
     menu: Menu,
     context: Option<serde_yaml::Value>,
+    config: SiteConfig,
+    render_options: RenderOptions,
+    file_types: FileTypes,
+
+    /// URL of the mermaid runtime loaded by pages with diagrams, if the
+    /// template has one or `site.yaml` sets it.
+    mermaid_runtime: Option<String>,
+
+    /// Folders listed by `render_folder`, computed once.
+    folders: Vec<Vec<String>>,
+
+    /// Attachments referenced by the pages rendered so far.
+    referenced_files: RefCell<HashSet<ItemPath>>,
+
+    /// Files written by this build.
+    outputs: RefCell<Manifest>,
 }
 
 impl<'a> Site<'a> {
+    /// Site of `vault`, with the raw `site.yaml` as `context` and the
+    /// options read from it as `config`, as given by `read_context`.
     pub fn new<P: AsRef<Path>>(
         vault: &'a Vault,
         template_dir: P,
         output_directory: P,
         base_url: String,
-        context_filepath: P,
-    ) -> Result<Self, SiteError> {
+        context: Option<serde_yaml::Value>,
+        config: SiteConfig,
+    ) -> Self {
         let mut env = Environment::new();
+        let vendored_mermaid = template_dir
+            .as_ref()
+            .join(VENDORED_MERMAID_RUNTIME)
+            .exists();
         env.set_loader(path_loader(template_dir));
+        add_date_filters(&mut env);
 
-        let context = {
-            if let Ok(file) = File::open(&context_filepath) {
-                Some(serde_yaml::from_reader(file)?)
-            } else {
-                eprintln!("failed to open {}", &context_filepath.as_ref().display());
-                None
-            }
+        let render_options = config.render_options();
+        let file_types = FileTypes::new(&config.file_types);
+        let menu = Site::build_menu(vault, &config);
+        let mermaid_runtime = match &config.mermaid.runtime {
+            Some(runtime) => Some(runtime.clone()),
+            None if vendored_mermaid => Some(format!("{base_url}{VENDORED_MERMAID_RUNTIME}")),
+            None => None,
         };
 
-        let menu = Site::build_menu(vault);
-
-        Ok(Self {
+        Self {
             vault,
             env,
             output_directory: output_directory.as_ref().to_path_buf(),
             base_url,
             context,
+            config,
+            render_options,
+            file_types,
+            mermaid_runtime,
+            folders: vault_folders(vault),
+            referenced_files: RefCell::new(HashSet::new()),
+            outputs: RefCell::new(Manifest::default()),
             menu,
-        })
+        }
+    }
+
+    /// Mermaid runtime of the page at `page`, with diagrams left to it. Their
+    /// source is shown as it is without one.
+    fn mermaid_runtime(&self, page: &str) -> Option<&str> {
+        if self.mermaid_runtime.is_none() {
+            eprintln!(
+                "warning: {page} has mermaid diagrams, but the template has no \
+                {VENDORED_MERMAID_RUNTIME} and `mermaid.runtime` is not set"
+            );
+        }
+        self.mermaid_runtime.as_deref()
+    }
+
+    /// Search backend used by the templates, if any.
+    fn search_backend(&self) -> Option<&'static str> {
+        if self.config.pagefind {
+            Some("pagefind")
+        } else if self.config.search {
+            Some("builtin")
+        } else {
+            None
+        }
     }
 
     fn render_note_string(&self, path: &ItemPath) -> Result<String, SiteRenderError> {
@@ -58,129 +145,796 @@ impl<'a> Site<'a> {
             .ok_or(SiteRenderError::NoteNotFound)?;
 
         let page_tmpl = self.env.get_template("page.html")?;
+        let rendered = self.with_links(path, true, |links| {
+            note.render_html(&self.render_options, Some(links))
+        })?;
+        let (published, updated) = note_dates(path, note);
 
-        let mut html = page_tmpl
-            .render(context! {
-                base_url => self.base_url,
-                note => note,
-                path => path,
-                note_html => note.render_html(),
-                menu => self.menu,
-                graph => self.vault.local_graph(path, 2),
-                site => self.context,
-            })
-            .unwrap();
+        let html = page_tmpl.render(context! {
+            base_url => self.base_url,
+            note => note,
+            path => path,
+            note_html => rendered.html,
+            published => published,
+            updated => updated,
+            menu => self.menu,
+            graph => self.vault.local_graph(path, 2),
+            site => self.context,
+            search => self.search_backend(),
+            mermaid => rendered
+                .mermaid
+                .then(|| self.mermaid_runtime(&path.to_string()))
+                .flatten(),
+        })?;
 
-        for wikilink in note.links.iter() {
-            if wikilink.embedded {
-                let (target, fragment) = wikilink
-                    .target
-                    .split_once('#')
-                    .unwrap_or((&wikilink.target, ""));
+        Ok(html)
+    }
 
-                if let Some((item_path, embedded_file)) = self.vault.resolve_embedded_link(target) {
-                    println!("resolved file: {}", item_path);
+    /// Run `render` with a resolver for the links of markdown written in the
+    /// vault item at `from`, which copies the files it links to.
+    ///
+    /// Canvases are only embedded when `embed_canvases` is set, so a canvas
+    /// can not end up embedding itself.
+    fn with_links<T>(
+        &self,
+        from: &ItemPath,
+        embed_canvases: bool,
+        render: impl FnOnce(&dyn LinkResolver) -> T,
+    ) -> Result<T, SiteRenderError> {
+        let links = SiteLinks {
+            site: self,
+            from,
+            embed_canvases,
+            error: RefCell::new(None),
+        };
+        let rendered = render(&links);
 
-                    let url = format!("{}{}", &self.base_url, &item_path);
+        match links.error.into_inner() {
+            Some(err) => Err(err),
+            None => Ok(rendered),
+        }
+    }
 
-                    let embedded_html = embedded_file_html(embedded_file, &url, fragment);
-                    html = html.replace(&format!("{wikilink}"), &embedded_html);
+    /// Events for a wikilink: a link to a page, or an embedded file, canvas
+    /// or drawing.
+    fn wikilink_events(
+        &self,
+        wikilink: &Wikilink,
+        embed_canvases: bool,
+    ) -> Result<Option<Vec<Event<'static>>>, SiteRenderError> {
+        if !wikilink.embedded {
+            let url = if let Some(item_path) = self.vault.resolve_link(&wikilink.target) {
+                format!("{}{}.html", &self.base_url, &item_path)
+            } else if let Some((item_path, _)) = self.vault.resolve_embedded_link(&wikilink.target)
+            {
+                self.reference_file(item_path.clone());
+                format!("{}{}", &self.base_url, &item_path)
+            } else {
+                return Ok(None);
+            };
 
-                    let path: PathBuf = item_path.into();
-                    let mut source = self.vault.root.clone();
-                    source.push(&path);
+            let label = wikilink.label.as_ref().unwrap_or(&wikilink.target);
+            let title = label.replace("~~", "").replace("==", "");
+            let tag = Tag::Link(LinkType::Inline, url.into(), title.into());
 
-                    let mut target = self.output_directory.clone();
-                    target.push(&path);
+            return Ok(Some(vec![
+                Event::Start(tag.clone()),
+                Event::Html(render_label(label).into()),
+                Event::End(tag),
+            ]));
+        }
 
-                    if let Some(parent) = target.parent() {
-                        if !parent.exists() {
-                            std::fs::create_dir_all(parent)?;
-                        }
-                    }
+        let (target, fragment) = wikilink
+            .target
+            .split_once('#')
+            .unwrap_or((&wikilink.target, ""));
+
+        let html = if let Some((item_path, embedded_file)) =
+            self.vault.resolve_embedded_link(target)
+        {
+            let url = format!("{}{}", &self.base_url, &item_path);
+            let file_type = self.file_types.get(&embedded_file.extension());
+            self.reference_file(item_path.clone());
+            let image = self.responsive_image(item_path, embedded_file);
+
+            embedded_file_html(
+                file_type,
+                &url,
+                fragment,
+                &wikilink.embed_options(),
+                image.as_ref(),
+            )
+        } else if let Some((item_path, canvas)) = self
+            .vault
+            .resolve_canvas_link(target)
+            .filter(|_| embed_canvases)
+        {
+            format!(
+                "<div class=\"canvas-embed\">\n{}<a href=\"{}{}.html\" class=\"canvas-open\">Open canvas</a>\n</div>",
+                self.canvas_board_html(&item_path, canvas)?,
+                &self.base_url,
+                &item_path
+            )
+        } else if let Some((item_path, drawing)) = self.vault.resolve_drawing_link(target) {
+            format!(
+                "<a href=\"{}{}.html\" class=\"excalidraw-embed\">{}</a>",
+                &self.base_url,
+                &item_path,
+                drawing.to_svg()
+            )
+        } else {
+            return Ok(None);
+        };
+
+        Ok(Some(vec![Event::Html(html.into())]))
+    }
+
+    /// URL of the vault item a markdown link or image written in the item at
+    /// `from` points to.
+    fn markdown_link_url(&self, from: &ItemPath, dest: &str) -> Option<String> {
+        let item_path = self.vault.resolve_markdown_link(from, dest)?;
+
+        let url = match self.vault.files.get(&item_path) {
+            Some(_) => {
+                self.reference_file(item_path.clone());
+                format!("{}{}", &self.base_url, &item_path)
+            }
+            None => {
+                let fragment = dest.find('#').map(|pos| &dest[pos..]).unwrap_or("");
+                format!("{}{}.html{}", &self.base_url, &item_path, fragment)
+            }
+        };
+
+        Some(url)
+    }
+
+    /// Record a file written to the output directory, to be kept by
+    /// `write_manifest`.
+    fn record_output(&self, path: &Path) {
+        if let Ok(path) = path.strip_prefix(&self.output_directory) {
+            self.outputs.borrow_mut().insert(path);
+        }
+    }
+
+    /// Results of a query block in the item at `from`, or the error in it.
+    fn query_html(&self, from: &ItemPath, source: &str) -> String {
+        match Query::parse(source) {
+            Ok(query) => query
+                .run(
+                    self.vault
+                        .notes
+                        .iter()
+                        .map(|(path, item)| (path, &item.note)),
+                )
+                .to_html(|path| format!("{}{}.html", &self.base_url, path)),
+            Err(err) => {
+                eprintln!("invalid query in {from}: {err}");
+                format!(
+                    "<pre class=\"query-error\">{}</pre>\n",
+                    escape_html_string(&format!("Invalid query: {err}\n\n{source}"))
+                )
+            }
+        }
+    }
+
+    /// Record a file referenced by a page, to be copied by
+    /// `copy_attachments`.
+    fn reference_file(&self, item_path: ItemPath) {
+        self.referenced_files.borrow_mut().insert(item_path);
+    }
 
-                    println!("copying {} -> {}", source.display(), target.display());
-                    std::fs::copy(source, target)?;
+    /// Copy the attachments referenced by the rendered pages, and the other
+    /// ones of a known type when `copy_all_attachments` is set, to the output
+    /// directory, warning about large and unreferenced ones. Other files in
+    /// the vault, such as build artifacts, are only copied when referenced.
+    ///
+    /// Files are hardlinked when the output directory is on the same file
+    /// system as the vault, and copied otherwise.
+    pub fn copy_attachments(&self) -> Result<(), SiteRenderError> {
+        let referenced = self.referenced_files.borrow();
+        let mut paths: Vec<&ItemPath> = if self.config.copy_all_attachments {
+            self.vault
+                .files
+                .iter()
+                .filter(|(item_path, file)| {
+                    referenced.contains(*item_path) || self.is_known_attachment(file)
+                })
+                .map(|(item_path, _)| item_path)
+                .collect()
+        } else {
+            referenced.iter().collect()
+        };
+        paths.sort();
+
+        let large_size = self.config.large_attachment_mb * 1024 * 1024;
+
+        for item_path in paths {
+            let embedded_file = match self.vault.files.get(item_path) {
+                Some(embedded_file) => embedded_file,
+                None => continue,
+            };
+
+            let source = embedded_file.path();
+            let size = std::fs::metadata(source)?.len();
+            if large_size > 0 && size > large_size {
+                eprintln!(
+                    "warning: {} is large ({:.1} MB)",
+                    item_path,
+                    size as f64 / (1024.0 * 1024.0)
+                );
+            }
+
+            let path: PathBuf = item_path.clone().into();
+            let target = self.output_directory.join(path);
+            println!("copying {} -> {}", source.display(), target.display());
+            link_or_copy(source, &target)?;
+            self.record_output(&target);
+        }
+
+        if !self.config.copy_all_attachments {
+            let mut unreferenced: Vec<&ItemPath> = self
+                .vault
+                .files
+                .iter()
+                .filter(|(item_path, file)| {
+                    !referenced.contains(*item_path) && self.is_known_attachment(file)
+                })
+                .map(|(item_path, _)| item_path)
+                .collect();
+            unreferenced.sort();
+
+            if !unreferenced.is_empty() {
+                eprintln!(
+                    "warning: {} attachments are not referenced by any page:",
+                    unreferenced.len()
+                );
+                for item_path in unreferenced {
+                    eprintln!("  {}", item_path);
                 }
-            } else if let Some(note_path) = self.vault.resolve_link(&wikilink.target) {
-                let label = wikilink.label.as_ref().unwrap_or(&wikilink.target);
-                let href = format!("/{}.html", &note_path);
-                let a_tag =
-                    format!("<a href=\"{href}\" title=\"{label}\" class=\"wikilink\">{label}</a>",);
-                html = html.replace(&format!("{wikilink}"), &a_tag);
             }
         }
 
-        Ok(html)
+        Ok(())
+    }
+
+    /// Whether `file` is of a built-in or custom attachment type.
+    fn is_known_attachment(&self, file: &EmbeddedFile) -> bool {
+        self.file_types.contains(&file.extension())
+    }
+
+    /// Resized and transcoded copies of a copied image, when enabled in
+    /// `site.yaml`. Images that fail to process are embedded as they are.
+    fn responsive_image(
+        &self,
+        item_path: ItemPath,
+        embedded_file: &EmbeddedFile,
+    ) -> Option<ResponsiveImage> {
+        let file_type = self.file_types.get(&embedded_file.extension());
+        if !self.config.images.enabled() || file_type.embed != EmbedKind::Image {
+            return None;
+        }
+
+        let path: PathBuf = item_path.into();
+        let target = self.output_directory.join(path);
+        if let Some(parent) = target.parent() {
+            if let Err(err) = std::fs::create_dir_all(parent) {
+                eprintln!("failed to create {}: {err}", parent.display());
+                return None;
+            }
+        }
+
+        match ResponsiveImage::generate(embedded_file.path(), &target, &self.config.images) {
+            Ok(image) => {
+                if let Some(image) = &image {
+                    let names = image
+                        .fallback
+                        .iter()
+                        .chain(image.sources.iter().flat_map(|source| source.srcset.iter()));
+                    for (name, _) in names {
+                        self.record_output(&target.with_file_name(name));
+                    }
+                }
+                image
+            }
+            Err(err) => {
+                eprintln!("failed to process {}: {err}", target.display());
+                None
+            }
+        }
+    }
+
+    /// Cards and edges of a canvas, with its text nodes rendered as markdown
+    /// and its file nodes linked to the vault items they reference.
+    fn canvas_board_html(
+        &self,
+        path: &ItemPath,
+        canvas: &Canvas,
+    ) -> Result<String, SiteRenderError> {
+        render_board(canvas, |node| match &node.kind {
+            CanvasNodeKind::Text { text } => {
+                let rendered = self.with_links(path, false, |links| {
+                    render_markdown_with_links(text, &self.render_options, Some(links))
+                })?;
+                Ok(rendered.html)
+            }
+            CanvasNodeKind::File { file, .. } => match self.vault.resolve_canvas_file(file) {
+                Some(item_path) => match self.vault.files.get(&item_path) {
+                    Some(embedded_file) => {
+                        let url = format!("{}{}", &self.base_url, &item_path);
+                        let file_type = self.file_types.get(&embedded_file.extension());
+                        self.reference_file(item_path.clone());
+                        let image = self.responsive_image(item_path, embedded_file);
+                        Ok(embedded_file_html(
+                            file_type,
+                            &url,
+                            "",
+                            &EmbedOptions::default(),
+                            image.as_ref(),
+                        ))
+                    }
+                    None => Ok(format!(
+                        "<a href=\"{}{}.html\" class=\"wikilink\">{}</a>\n",
+                        &self.base_url,
+                        &item_path,
+                        escape_html_string(&file_title(file))
+                    )),
+                },
+                None => Ok(format!(
+                    "<span class=\"canvas-missing\">{}</span>\n",
+                    escape_html_string(&file_title(file))
+                )),
+            },
+            _ => Ok(default_node_html(node)),
+        })
+    }
+
+    pub fn render_canvas(&self, path: &ItemPath) -> Result<(), SiteRenderError> {
+        let canvas = self
+            .vault
+            .get_canvas(path)
+            .ok_or(SiteRenderError::NoteNotFound)?;
+
+        let canvas_tmpl = self.env.get_template("canvas.html")?;
+        let html = canvas_tmpl.render(context! {
+            base_url => self.base_url,
+            note => context! { title => file_title(&path.to_string()) },
+            path => path,
+            canvas_html => self.canvas_board_html(path, canvas)?,
+            menu => self.menu,
+            graph => self.vault.local_graph(path, 2),
+            site => self.context,
+            search => self.search_backend(),
+        })?;
+
+        let output_path = self.output_directory.join(format!("{}.html", path));
+        std::fs::create_dir_all(output_path.parent().unwrap())?;
+        std::fs::write(&output_path, html)?;
+        self.record_output(&output_path);
+
+        Ok(())
+    }
+
+    pub fn render_drawing(&self, path: &ItemPath) -> Result<(), SiteRenderError> {
+        let drawing = self
+            .vault
+            .get_drawing(path)
+            .ok_or(SiteRenderError::NoteNotFound)?;
+
+        let drawing_tmpl = self.env.get_template("drawing.html")?;
+        let html = drawing_tmpl.render(context! {
+            base_url => self.base_url,
+            note => context! { title => file_title(&path.to_string()) },
+            path => path,
+            drawing_html => drawing.to_svg(),
+            menu => self.menu,
+            graph => self.vault.local_graph(path, 2),
+            site => self.context,
+            search => self.search_backend(),
+        })?;
+
+        let output_path = self.output_directory.join(format!("{}.html", path));
+        std::fs::create_dir_all(output_path.parent().unwrap())?;
+        std::fs::write(&output_path, html)?;
+        self.record_output(&output_path);
+
+        Ok(())
     }
 
     pub fn render_note(&self, path: &ItemPath) -> Result<(), SiteRenderError> {
+        // written by `render_folder`
+        if self.is_folder_index(path) {
+            return Ok(());
+        }
+
         let html = self.render_note_string(path)?;
 
         let filename = format!("{}.html", path);
         let output_path = self.output_directory.join(filename);
 
         std::fs::create_dir_all(output_path.parent().unwrap())?;
-        std::fs::write(output_path, html)?;
+        std::fs::write(&output_path, html)?;
+        self.record_output(&output_path);
 
         Ok(())
     }
 
-    fn build_menu(vault: &Vault) -> Menu {
-        let mut paths: Vec<ItemPath> = vault.notes.keys().cloned().collect();
-        paths.sort();
+    /// Write an Atom feed of the notes with dates, when `feed` is set in
+    /// `site.yaml`.
+    pub fn write_feed(&self) -> Result<(), SiteRenderError> {
+        let Some(config) = &self.config.feed else {
+            return Ok(());
+        };
+
+        let site_url = if self.base_url.contains("://") {
+            self.base_url.clone()
+        } else {
+            format!("{}{}", config.url.trim_end_matches('/'), self.base_url)
+        };
+        let entries = self
+            .vault
+            .notes
+            .iter()
+            .filter_map(|(path, item)| {
+                let note = &item.note;
+                let (published, updated) = note_dates(path, note);
+                Some(FeedEntry {
+                    title: note.title.clone(),
+                    url: format!("{site_url}{path}.html"),
+                    published: published.or(updated)?,
+                    updated: updated.or(published)?,
+                    tags: note.tags.clone(),
+                })
+            })
+            .collect();
 
-        let mut menu: Menu = Menu::new();
+        let title = self
+            .context
+            .as_ref()
+            .and_then(|context| context.get("title"))
+            .and_then(|title| title.as_str())
+            .unwrap_or("Notes");
 
-        for current_path in paths {
-            menu.add_path(&current_path);
+        let output_path = self.output_directory.join(FEED_FILE);
+        std::fs::create_dir_all(&self.output_directory)?;
+        let author = config.author.clone().unwrap_or_else(|| FeedAuthor {
+            name: title.to_string(),
+            email: None,
+            uri: None,
+        });
+        std::fs::write(
+            &output_path,
+            atom_feed(title, &site_url, &author, entries, config.limit),
+        )?;
+        self.record_output(&output_path);
+
+        Ok(())
+    }
+
+    /// Folders of the vault, as `vault_folders` lists them.
+    pub fn folders(&self) -> &[Vec<String>] {
+        &self.folders
+    }
+
+    /// Notes, canvases and drawings.
+    fn page_paths(&self) -> impl Iterator<Item = &ItemPath> {
+        self.vault
+            .notes
+            .keys()
+            .chain(self.vault.canvases.keys())
+            .chain(self.vault.drawings.keys())
+    }
+
+    /// Write the `index.html` of a folder, listing its pages and subfolders
+    /// below the folder's `folder` note, if it has one. Folders with an
+    /// `index` note are left alone: the page of the note is their index.
+    ///
+    /// The root folder's page is the `home` note when one is set.
+    pub fn render_folder(&self, folder: &[String]) -> Result<(), SiteRenderError> {
+        let output_path = self
+            .output_directory
+            .join(folder.join("/"))
+            .join("index.html");
+
+        let html = match self.home_note(folder) {
+            Some(home) => self.render_note_string(&home)?,
+            None => self.render_folder_string(folder)?,
+        };
+
+        std::fs::create_dir_all(output_path.parent().unwrap())?;
+        std::fs::write(&output_path, html)?;
+        self.record_output(&output_path);
+
+        Ok(())
+    }
+
+    /// Note set as `home` in `site.yaml`, for the root folder.
+    fn home_note(&self, folder: &[String]) -> Option<ItemPath> {
+        let home = self.config.home.as_deref().filter(|_| folder.is_empty())?;
+        let item_path = self
+            .vault
+            .resolve_link(home)
+            .filter(|item_path| self.vault.notes.contains_key(item_path));
+        match &item_path {
+            None => eprintln!("home note {home} not found, listing the vault instead"),
+            Some(item_path) => {
+                let index = ItemPath::Absolute(vec![FOLDER_INDEX_NOTE.to_string()]);
+                if *item_path != index && self.vault.notes.contains_key(&index) {
+                    eprintln!("the home note is set, the vault's index note is not shown");
+                }
+            }
         }
+        item_path
+    }
 
-        menu
+    /// Note shown above the listing of `folder`: its `index.md`, or else its
+    /// `folder.md`.
+    fn folder_intro(&self, folder: &[String]) -> Option<(ItemPath, &Note)> {
+        [FOLDER_INDEX_NOTE, FOLDER_INTRO_NOTE]
+            .iter()
+            .find_map(|name| {
+                let mut components = folder.to_vec();
+                components.push(name.to_string());
+                let path = ItemPath::Absolute(components);
+                self.vault.get_note(&path).map(|note| (path, note))
+            })
     }
-}
 
-#[derive(Serialize, Debug)]
-struct Menu {
-    #[serde(flatten)]
-    items: HashMap<String, MenuItem>,
-}
+    /// Whether `path` is the `index.md` of a folder, written as the folder's
+    /// listing instead of a page of its own.
+    fn is_folder_index(&self, path: &ItemPath) -> bool {
+        matches!(path, ItemPath::Absolute(components)
+            if components.last().is_some_and(|name| name == FOLDER_INDEX_NOTE))
+            && self.vault.notes.contains_key(path)
+    }
 
-impl Menu {
-    fn new() -> Self {
-        Self {
-            items: HashMap::new(),
+    fn render_folder_string(&self, folder: &[String]) -> Result<String, SiteRenderError> {
+        let intro = self.folder_intro(folder);
+
+        let rendered = match &intro {
+            Some((path, note)) => Some(self.with_links(path, true, |links| {
+                note.render_html(&self.render_options, Some(links))
+            })?),
+            None => None,
+        };
+
+        let title = folder.last().map(String::as_str).unwrap_or("Index");
+
+        let list_tmpl = self.env.get_template("list.html")?;
+        let html = list_tmpl.render(context! {
+            base_url => self.base_url,
+            note => context! { title => title },
+            path => folder.join("/"),
+            intro_html => rendered.as_ref().map(|rendered| &rendered.html),
+            entries => self.folder_entries(folder),
+            menu => self.menu,
+            graph => match &intro {
+                Some((path, _)) => self.vault.local_graph(path, 2),
+                None => Some(StableGraph::new()),
+            },
+            site => self.context,
+            search => self.search_backend(),
+            mermaid => rendered
+                .filter(|rendered| rendered.mermaid)
+                .and_then(|_| self.mermaid_runtime(&folder.join("/"))),
+        })?;
+
+        Ok(html)
+    }
+
+    /// Subfolders and pages of a folder, subfolders first, without the
+    /// folder's intro note.
+    fn folder_entries(&self, folder: &[String]) -> Vec<FolderEntry> {
+        let intro = self.folder_intro(folder);
+        let in_folder = |components: &[String]| {
+            components.len() == folder.len() + 1 && components.starts_with(folder)
+        };
+
+        let mut folders: Vec<FolderEntry> = self
+            .folders
+            .iter()
+            .filter(|components| in_folder(components))
+            .map(|components| FolderEntry {
+                title: components[folder.len()].clone(),
+                href: format!("{}{}/index.html", self.base_url, components.join("/")),
+                kind: "folder",
+                date: None,
+                tags: Vec::new(),
+            })
+            .collect();
+
+        let mut pages: Vec<FolderEntry> = self
+            .page_paths()
+            .filter_map(|path| match path {
+                ItemPath::Absolute(components) if in_folder(components) => Some((path, components)),
+                _ => None,
+            })
+            .filter(|(path, _)| intro.as_ref().map(|(intro, _)| intro) != Some(path))
+            .map(|(path, components)| {
+                let href = format!("{}{}.html", self.base_url, path);
+                match self.vault.get_note(path) {
+                    Some(note) => FolderEntry {
+                        title: note.title.clone(),
+                        href,
+                        kind: "note",
+                        date: note_dates(path, note).0,
+                        tags: note.tags.clone(),
+                    },
+                    None => FolderEntry {
+                        title: file_title(&components[folder.len()]),
+                        href,
+                        kind: if self.vault.canvases.contains_key(path) {
+                            "canvas"
+                        } else {
+                            "drawing"
+                        },
+                        date: None,
+                        tags: Vec::new(),
+                    },
+                }
+            })
+            .collect();
+
+        // the most recent notes first, then the ones without a date
+        folders.sort_by(|a, b| a.title.cmp(&b.title));
+        pages.sort_by(|a, b| b.date.cmp(&a.date).then_with(|| a.title.cmp(&b.title)));
+        folders.extend(pages);
+        folders
+    }
+
+    /// Write the built-in search index to `_search` in the output directory.
+    pub fn write_search_index(&self) -> Result<(), SiteRenderError> {
+        if self.search_backend() != Some("builtin") {
+            return Ok(());
+        }
+
+        let mut index = SearchIndex::new();
+        for (path, item) in self.vault.notes.iter() {
+            index.add_note(path, &item.note);
+        }
+
+        // the index is written from scratch, so shards of removed terms go
+        let search_directory = self.output_directory.join("_search");
+        if search_directory.exists() {
+            std::fs::remove_dir_all(&search_directory)?;
         }
+        index.write(&search_directory)?;
+        self.outputs
+            .borrow_mut()
+            .insert_directory(&self.output_directory, "_search");
+
+        Ok(())
     }
 
-    fn add_path(&mut self, path: &ItemPath) {
-        if let ItemPath::Absolute(components) = path {
-            let mut current_menu = self;
-            for component in &components[..components.len() - 1] {
-                let menu = current_menu
-                    .items
-                    .entry(component.clone())
-                    .or_insert(MenuItem::Folder(Menu::new()));
-                match menu {
-                    MenuItem::Page(_) => todo!(),
-                    MenuItem::Folder(menu) => current_menu = menu,
+    /// Remove the files the previous build wrote and this one did not, and
+    /// write the manifest of this build. Call it once everything else,
+    /// including `_static`, is written.
+    ///
+    /// With `force`, every file this build did not write is removed, for
+    /// output directories without the manifest of a previous build.
+    pub fn write_manifest(&self, force: bool) -> Result<(), SiteRenderError> {
+        let mut outputs = self.outputs.borrow_mut();
+        outputs.insert_directory(&self.output_directory, "_static");
+
+        let keep = &self.config.keep;
+        let previous = if force {
+            Some(Manifest::from_directory(&self.output_directory))
+        } else {
+            let previous = Manifest::load(&self.output_directory);
+            if previous.is_none() {
+                let found = Manifest::from_directory(&self.output_directory);
+                let unknown = outputs.stale(&found, keep).count();
+                if unknown > 0 {
+                    eprintln!(
+                        "warning: {} has no manifest of a previous build, so the {unknown} \
+                        files this build did not write were left; build with --force to \
+                        remove them",
+                        self.output_directory.display()
+                    );
                 }
             }
+            previous
+        };
 
-            let filename = &components[components.len() - 1];
-            current_menu
-                .items
-                .entry(filename.clone())
-                .or_insert(MenuItem::Page(path.clone()));
+        if let Some(previous) = previous {
+            for path in outputs.remove_stale(&previous, &self.output_directory, keep)? {
+                println!("removing {}", path.display());
+            }
         }
+
+        outputs.write(&self.output_directory)?;
+
+        Ok(())
+    }
+
+    fn build_menu(vault: &Vault, config: &SiteConfig) -> Menu {
+        let menu_page = |path: ItemPath| {
+            if let Some(note) = vault.get_note(&path) {
+                let metadata = &note.metadata;
+                MenuPage {
+                    title: metadata_field(&path, metadata.get_str("menu_title"))
+                        .unwrap_or(&note.title)
+                        .to_string(),
+                    order: metadata_field(&path, metadata.get_number("order"))
+                        .or_else(|| metadata_field(&path, metadata.get_number("weight"))),
+                    hidden: metadata_field(&path, metadata.get_bool("hide_from_menu"))
+                        .unwrap_or(false),
+                    path,
+                }
+            } else {
+                MenuPage {
+                    title: file_title(&path.to_string()),
+                    order: None,
+                    hidden: false,
+                    path,
+                }
+            }
+        };
+
+        match &config.menu.tree {
+            Some(tree) => Menu::from_tree(tree, &config.menu, &|link| {
+                vault.resolve_link(link).map(menu_page)
+            }),
+            None => {
+                let pages = vault
+                    .notes
+                    .keys()
+                    .chain(vault.canvases.keys())
+                    .chain(vault.drawings.keys())
+                    .cloned()
+                    .map(menu_page)
+                    .collect();
+                Menu::from_pages(pages, &config.menu)
+            }
+        }
+    }
+}
+
+/// Resolves the links of markdown written in the vault item at `from`.
+struct SiteLinks<'s, 'a> {
+    site: &'s Site<'a>,
+    from: &'s ItemPath,
+    embed_canvases: bool,
+
+    /// First error hit while rendering an embed, reported once the markdown
+    /// is rendered.
+    error: RefCell<Option<SiteRenderError>>,
+}
+
+impl SiteLinks<'_, '_> {
+    fn ok_or_record<T>(&self, result: Result<Option<T>, SiteRenderError>) -> Option<T> {
+        result.unwrap_or_else(|err| {
+            self.error.borrow_mut().get_or_insert(err);
+            None
+        })
+    }
+}
+
+impl LinkResolver for SiteLinks<'_, '_> {
+    fn wikilink(&self, link: &Wikilink) -> Option<Vec<Event<'static>>> {
+        self.ok_or_record(self.site.wikilink_events(link, self.embed_canvases))
+    }
+
+    fn markdown_link(&self, dest: &str) -> Option<String> {
+        self.site.markdown_link_url(self.from, dest)
+    }
+
+    fn query(&self, source: &str) -> Option<String> {
+        Some(self.site.query_html(self.from, source))
     }
 }
 
-#[derive(Serialize, Debug)]
-#[serde(untagged)]
-enum MenuItem {
-    Page(ItemPath),
-    Folder(Menu),
+/// A subfolder or page on a folder's page.
+#[derive(Serialize, Debug, PartialEq)]
+struct FolderEntry {
+    title: String,
+    href: String,
+
+    /// `folder`, `note`, `canvas` or `drawing`.
+    kind: &'static str,
+
+    /// Publication date of a note.
+    date: Option<Date>,
+    tags: Vec<String>,
 }
 
 #[derive(Error, Debug)]
@@ -197,57 +951,494 @@ pub(crate) enum SiteRenderError {
 
 #[derive(Error, Debug)]
 pub(crate) enum SiteError {
-    #[error("context error")]
+    #[error("{0}")]
     InvalidContext(#[from] serde_yaml::Error),
 }
 
-fn embedded_file_html(file: &EmbeddedFile, url: &str, fragment: &str) -> String {
-    match file {
-        EmbeddedFile::Image(_) => format!(r#"<img src="{}">"#, url),
-        EmbeddedFile::Audio(_) => format!(r#"<audio src="{}" controls></audio>"#, url),
-        EmbeddedFile::Video(_) => format!(r#"<video src="{}" controls></video>"#, url),
-        EmbeddedFile::Pdf(_) => {
+/// The `site.yaml` at `path`, passed to templates as `site`, and the options
+/// in it. Without the file, there is no context and the options are the
+/// defaults.
+pub(crate) fn read_context<P: AsRef<Path>>(
+    path: P,
+) -> Result<(Option<serde_yaml::Value>, SiteConfig), SiteError> {
+    let context: Option<serde_yaml::Value> = match File::open(&path) {
+        Ok(file) => Some(serde_yaml::from_reader(file)?),
+        Err(_) => {
+            eprintln!("failed to open {}", path.as_ref().display());
+            None
+        }
+    };
+
+    let config = match &context {
+        Some(value) => SiteConfig::try_from(value)?,
+        None => SiteConfig::default(),
+    };
+
+    Ok((context, config))
+}
+
+/// Folders with notes, canvases or drawings, and their parent folders,
+/// starting with the vault's root.
+fn vault_folders(vault: &Vault) -> Vec<Vec<String>> {
+    let mut folders: BTreeSet<Vec<String>> = BTreeSet::new();
+    folders.insert(Vec::new());
+
+    let paths = vault
+        .notes
+        .keys()
+        .chain(vault.canvases.keys())
+        .chain(vault.drawings.keys());
+    for path in paths {
+        if let ItemPath::Absolute(components) = path {
+            for end in 1..components.len() {
+                folders.insert(components[..end].to_vec());
+            }
+        }
+    }
+
+    folders.into_iter().collect()
+}
+
+/// Publication and last update dates of the note at `path`, from its
+/// metadata.
+fn note_dates(path: &ItemPath, note: &Note) -> (Option<Date>, Option<Date>) {
+    let first_date = |fields: &[&str]| {
+        fields
+            .iter()
+            .find_map(|field| metadata_field(path, note.metadata.get_date(field)))
+    };
+    (first_date(&PUBLISHED_FIELDS), first_date(&UPDATED_FIELDS))
+}
+
+/// A field of the metadata of the note at `path`, reporting values of the
+/// wrong type.
+fn metadata_field<T>(path: &ItemPath, field: Result<Option<T>, MetadataError>) -> Option<T> {
+    field.unwrap_or_else(|err| {
+        eprintln!("{path}: {err}");
+        None
+    })
+}
+
+/// Display name of a vault file: its file name without the extension.
+fn file_title(file: &str) -> String {
+    let filename = file.rsplit('/').next().unwrap_or(file);
+    match filename.rsplit_once('.') {
+        Some((stem, _)) if !stem.is_empty() => stem.to_string(),
+        _ => filename.to_string(),
+    }
+}
+
+fn escape_html_string(text: &str) -> String {
+    let mut html = String::new();
+    escape_html(&mut html, text).unwrap();
+    html
+}
+
+/// Hardlink `source` to `target`, replacing it, or copy it when they are on
+/// different file systems.
+fn link_or_copy(source: &Path, target: &Path) -> Result<(), std::io::Error> {
+    if let Some(parent) = target.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+
+    match std::fs::remove_file(target) {
+        Err(err) if err.kind() != std::io::ErrorKind::NotFound => return Err(err),
+        _ => {}
+    }
+
+    if std::fs::hard_link(source, target).is_err() {
+        std::fs::copy(source, target)?;
+    }
+
+    Ok(())
+}
+
+fn escape_href_string(href: &str) -> String {
+    let mut escaped = String::new();
+    escape_href(&mut escaped, href).unwrap();
+    escaped
+}
+
+fn embedded_file_html(
+    file_type: &FileType,
+    url: &str,
+    fragment: &str,
+    options: &EmbedOptions,
+    image: Option<&ResponsiveImage>,
+) -> String {
+    let (width, height) = match (image, options.width, options.height) {
+        (_, Some(width), Some(height)) => (Some(width), Some(height)),
+        // keep the intrinsic aspect ratio
+        (Some(image), Some(width), None) => (
+            Some(width),
+            Some((image.height as u64 * width as u64 / image.width as u64) as u32),
+        ),
+        (Some(image), None, _) => (Some(image.width), Some(image.height)),
+        (None, width, height) => (width, height),
+    };
+
+    let mut size = String::new();
+    if let Some(width) = width {
+        size.push_str(&format!(r#" width="{}""#, width));
+    }
+    if let Some(height) = height {
+        size.push_str(&format!(r#" height="{}""#, height));
+    }
+
+    let name = url.rsplit('/').next().unwrap_or(url);
+    let mime = file_type.mime.as_deref().unwrap_or_default();
+
+    if let Some(html) = &file_type.html {
+        return html
+            .replace("{url}", &escape_href_string(url))
+            .replace("{name}", &escape_html_string(name))
+            .replace("{mime}", &escape_html_string(mime));
+    }
+
+    let alt = match &options.alt {
+        Some(alt) => alt.clone(),
+        None => file_title(name),
+    };
+
+    if let (EmbedKind::Image, Some(image)) = (file_type.embed, image) {
+        return picture_html(url, image, &alt, options.width, &size);
+    }
+
+    let url = escape_href_string(url);
+    let fragment = escape_href_string(fragment);
+
+    match file_type.embed {
+        EmbedKind::Image => format!(
+            r#"<img src="{}" alt="{}"{} loading="lazy">"#,
+            url,
+            escape_html_string(&alt),
+            size
+        ),
+        EmbedKind::Audio => format!(r#"<audio src="{}" controls></audio>"#, url),
+        EmbedKind::Video => format!(r#"<video src="{}"{} controls></video>"#, url, size),
+        EmbedKind::Pdf => {
             format!(
                 r#"<iframe src="{}#{}" frameborder="0"></iframe>"#,
                 url, fragment
             )
         }
+        EmbedKind::Download => {
+            let mut type_attribute = String::new();
+            if !mime.is_empty() {
+                type_attribute = format!(r#" type="{}""#, escape_html_string(mime));
+            }
+            let label = options.alt.as_deref().unwrap_or(name);
+            format!(
+                r#"<a href="{}" class="attachment"{} download>{}</a>"#,
+                url,
+                type_attribute,
+                escape_html_string(label)
+            )
+        }
+    }
+}
+
+/// `<picture>` with a source per format of a responsive image, and its
+/// copies in its own format as the `<img>` fallback.
+fn picture_html(
+    url: &str,
+    image: &ResponsiveImage,
+    alt: &str,
+    width: Option<u32>,
+    size: &str,
+) -> String {
+    let directory = url.rsplit_once('/').map(|(dir, _)| dir).unwrap_or("");
+    let srcset = |copies: &[(String, u32)]| {
+        copies
+            .iter()
+            .map(|(name, width)| {
+                format!(
+                    "{} {}w",
+                    escape_href_string(&format!("{directory}/{name}")),
+                    width
+                )
+            })
+            .collect::<Vec<String>>()
+            .join(", ")
+    };
+    let sizes = match width {
+        Some(width) => format!("(max-width: {width}px) 100vw, {width}px"),
+        None => image.sizes.clone(),
+    };
+    let sizes = escape_html_string(&sizes);
+
+    let mut html = String::from("<picture>");
+    for source in image.sources.iter() {
+        html.push_str(&format!(
+            r#"<source type="{}" srcset="{}" sizes="{}">"#,
+            source.mime_type,
+            srcset(&source.srcset),
+            sizes
+        ));
     }
+    html.push_str(&format!(
+        r#"<img src="{}" srcset="{}" sizes="{}" alt="{}"{} loading="lazy"></picture>"#,
+        escape_href_string(url),
+        srcset(&image.fallback),
+        sizes,
+        escape_html_string(alt),
+        size
+    ));
+
+    html
 }
 
 #[cfg(test)]
 mod tests {
-    use super::embedded_file_html;
-    use crate::vault::EmbeddedFile;
+    use super::{embedded_file_html, link_or_copy, Site};
+    use crate::{
+        attachment::{EmbedKind, FileType, FileTypes},
+        config::SiteConfig,
+        images::{ImageSource, ResponsiveImage},
+        vault::{ItemPath, Vault, VaultBuilder},
+        wikilink::EmbedOptions,
+    };
+    use std::path::PathBuf;
+
+    /// Vault in a temporary directory named after `name`, with `files` of
+    /// `(path, content)`.
+    fn test_vault(name: &str, files: &[(&str, &str)]) -> (PathBuf, Vault) {
+        let dir = std::env::temp_dir().join(format!("garden-{name}-{}", std::process::id()));
+        for (path, content) in files {
+            let path = dir.join(path);
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, content).unwrap();
+        }
+        let vault = VaultBuilder::new(&dir).build();
+        (dir, vault)
+    }
+
+    /// Site of `vault` with the default template, written into `dir`.
+    fn test_site<'a>(vault: &'a Vault, dir: &std::path::Path) -> Site<'a> {
+        let template = PathBuf::from(env!("CARGO_MANIFEST_DIR")).join("templates/default");
+        Site::new(
+            vault,
+            template,
+            dir.join("_output"),
+            "/".to_string(),
+            None,
+            SiteConfig::default(),
+        )
+    }
 
     #[test]
     fn embedded_file_image_html() {
-        let file = EmbeddedFile::Image("./files/image.webp".into());
-        let html = embedded_file_html(&file, "./files/image.webp", "");
-        assert_eq!(html, r#"<img src="./files/image.webp">"#);
+        let file_types = FileTypes::default();
+        let file = file_types.get("webp");
+        let options = EmbedOptions {
+            alt: Some("A \"cat\"".to_string()),
+            width: Some(300),
+            height: Some(200),
+        };
+        let html = embedded_file_html(file, "./files/image.webp", "", &options, None);
+        assert_eq!(
+            html,
+            r#"<img src="./files/image.webp" alt="A &quot;cat&quot;" width="300" height="200" loading="lazy">"#
+        );
+    }
+
+    #[test]
+    fn embedded_file_responsive_image_html() {
+        let file_types = FileTypes::default();
+        let file = file_types.get("jpg");
+        let image = ResponsiveImage {
+            width: 1600,
+            height: 1200,
+            fallback: vec![
+                ("image.jpg.480w.jpg".to_string(), 480),
+                ("image.jpg".to_string(), 1600),
+            ],
+            sources: vec![ImageSource {
+                mime_type: "image/webp",
+                srcset: vec![
+                    ("image.jpg.480w.webp".to_string(), 480),
+                    ("image.jpg.1600w.webp".to_string(), 1600),
+                ],
+            }],
+            sizes: "100vw".to_string(),
+        };
+        let options = EmbedOptions {
+            alt: Some("Cat".to_string()),
+            width: Some(400),
+            height: None,
+        };
+        let html = embedded_file_html(file, "/files/image.jpg", "", &options, Some(&image));
+        assert_eq!(
+            html,
+            "<picture>\
+            <source type=\"image/webp\" srcset=\"/files/image.jpg.480w.webp 480w, /files/image.jpg.1600w.webp 1600w\" sizes=\"(max-width: 400px) 100vw, 400px\">\
+            <img src=\"/files/image.jpg\" srcset=\"/files/image.jpg.480w.jpg 480w, /files/image.jpg 1600w\" sizes=\"(max-width: 400px) 100vw, 400px\" \
+            alt=\"Cat\" width=\"400\" height=\"300\" loading=\"lazy\"></picture>"
+        );
     }
 
     #[test]
     fn embedded_file_audio_html() {
-        let file = EmbeddedFile::Audio("./files/audio.ogg".into());
-        let html = embedded_file_html(&file, "./files/audio.ogg", "");
+        let file_types = FileTypes::default();
+        let file = file_types.get("ogg");
+        let html = embedded_file_html(
+            file,
+            "./files/audio.ogg",
+            "",
+            &EmbedOptions::default(),
+            None,
+        );
         assert_eq!(html, r#"<audio src="./files/audio.ogg" controls></audio>"#);
     }
 
     #[test]
     fn embedded_file_video_html() {
-        let file = EmbeddedFile::Video("./files/video.ogv".into());
-        let html = embedded_file_html(&file, "./files/video.ogv", "");
+        let file_types = FileTypes::default();
+        let file = file_types.get("ogv");
+        let html = embedded_file_html(
+            file,
+            "./files/video.ogv",
+            "",
+            &EmbedOptions::default(),
+            None,
+        );
         assert_eq!(html, r#"<video src="./files/video.ogv" controls></video>"#);
     }
 
     #[test]
     fn embedded_file_pdf_html() {
-        let file = EmbeddedFile::Pdf("./files/document.pdf".into());
-        let html = embedded_file_html(&file, "./files/document.pdf", "page=1");
+        let file_types = FileTypes::default();
+        let file = file_types.get("pdf");
+        let html = embedded_file_html(
+            file,
+            "./files/document.pdf",
+            "page=1",
+            &EmbedOptions::default(),
+            None,
+        );
         assert_eq!(
             html,
             r#"<iframe src="./files/document.pdf#page=1" frameborder="0"></iframe>"#
         );
     }
+
+    #[test]
+    fn embedded_file_download_html() {
+        let file_types = FileTypes::default();
+        let html = embedded_file_html(
+            file_types.get("csv"),
+            "/files/data & more.csv",
+            "",
+            &EmbedOptions::default(),
+            None,
+        );
+        assert_eq!(
+            html,
+            r#"<a href="/files/data%20&amp;%20more.csv" class="attachment" type="text/csv" download>data &amp; more.csv</a>"#
+        );
+    }
+
+    #[test]
+    fn embedded_file_custom_html() {
+        let file_type = FileType {
+            embed: EmbedKind::Download,
+            mime: None,
+            html: Some(r#"<iframe src="{url}" title="{name}"></iframe>"#.to_string()),
+        };
+        let html = embedded_file_html(
+            &file_type,
+            "/files/board.drawio",
+            "",
+            &EmbedOptions::default(),
+            None,
+        );
+        assert_eq!(
+            html,
+            r#"<iframe src="/files/board.drawio" title="board.drawio"></iframe>"#
+        );
+    }
+
+    #[test]
+    fn link_or_copy_replaces_target() {
+        let dir = std::env::temp_dir().join(format!("garden-site-{}", std::process::id()));
+        let source = dir.join("source.txt");
+        let target = dir.join("output").join("files").join("target.txt");
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(&source, "new").unwrap();
+
+        link_or_copy(&source, &target).unwrap();
+        std::fs::write(&source, "newer").unwrap();
+        link_or_copy(&source, &target).unwrap();
+        assert_eq!(std::fs::read_to_string(&target).unwrap(), "newer");
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn canvas_text_comments_are_stripped() {
+        let canvas = r#"{"nodes": [{"id": "1", "type": "text", "text": "Visible %%private card comment%%",
+            "x": 0, "y": 0, "width": 200, "height": 100}], "edges": []}"#;
+        let (dir, vault) = test_vault("canvas-comments", &[("Board.canvas", canvas)]);
+        let site = test_site(&vault, &dir);
+
+        let path = ItemPath::Absolute(vec!["Board.canvas".to_string()]);
+        let html = site
+            .canvas_board_html(&path, vault.get_canvas(&path).unwrap())
+            .unwrap();
+        assert!(html.contains("Visible"));
+        assert!(!html.contains("private"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn markdown_urls_and_fragments_are_kept() {
+        let (dir, vault) = test_vault(
+            "markdown-urls",
+            &[
+                ("Foo.md", "Foo"),
+                ("Projects.md", "Projects"),
+                (
+                    "Projects/Plan.md",
+                    "[x](https://example.com/Foo) [s](#Plan) [f](../Foo.md)",
+                ),
+            ],
+        );
+        let site = test_site(&vault, &dir);
+
+        let path = ItemPath::Absolute(vec!["Projects".to_string(), "Plan".to_string()]);
+        let html = site.render_note_string(&path).unwrap();
+        assert!(html.contains(r#"<a href="https://example.com/Foo">x</a>"#));
+        assert!(html.contains(r##"<a href="#Plan">s</a>"##));
+        assert!(html.contains(r#"<a href="/Foo.html">f</a>"#));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn index_note_is_folder_intro() {
+        let (dir, vault) = test_vault(
+            "folder-index",
+            &[
+                ("Projects/index.md", "Intro of the projects"),
+                ("Projects/Plan.md", "Plan"),
+            ],
+        );
+        let site = test_site(&vault, &dir);
+        let folder = vec!["Projects".to_string()];
+        let output = dir.join("_output/Projects/index.html");
+
+        // not a page of its own, which would be the folder's `index.html`
+        let index = ItemPath::Absolute(vec!["Projects".to_string(), "index".to_string()]);
+        site.render_note(&index).unwrap();
+        assert!(!output.exists());
+
+        site.render_folder(&folder).unwrap();
+        let html = std::fs::read_to_string(&output).unwrap();
+        assert!(html.contains("Intro of the projects"));
+        assert!(html.contains(r#"<a href="&#x2f;Projects&#x2f;Plan.html">Plan</a>"#));
+        assert!(!html.contains("Projects&#x2f;index.html\">"));
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
@@ -7,11 +7,20 @@ use std::{
 };
 use walkdir::WalkDir;
 
-use crate::note::Note;
+use crate::{
+    canvas::{file_target, Canvas},
+    excalidraw::Drawing,
+    note::{is_local_link, Note},
+    schema::{MetadataSchema, SchemaError},
+};
 
 pub(crate) struct VaultBuilder {
     pub directory: PathBuf,
     tags: Option<Vec<String>>,
+
+    /// Directories to skip, relative to the vault.
+    excluded: Vec<PathBuf>,
+    schema: Option<MetadataSchema>,
 }
 
 impl VaultBuilder {
@@ -19,6 +28,8 @@ impl VaultBuilder {
         Self {
             directory: directory.as_ref().to_path_buf(),
             tags: None,
+            excluded: Vec::new(),
+            schema: None,
         }
     }
 
@@ -26,16 +37,20 @@ impl VaultBuilder {
         let mut notes: HashMap<ItemPath, NoteItem> = HashMap::new();
         let mut graph = StableGraph::new();
         let mut files: HashMap<ItemPath, EmbeddedFile> = HashMap::new();
+        let mut canvases: HashMap<ItemPath, CanvasItem> = HashMap::new();
+        let mut drawings: HashMap<ItemPath, DrawingItem> = HashMap::new();
+        let mut unparsed_notes = Vec::new();
+        let mut metadata_errors = Vec::new();
 
         const MARKDOWN_FILE_EXTENSIONS: &[&str] = &[".md"];
-        const IMAGE_FILE_EXTENSIONS: &[&str] =
-            &[".png", ".webp", ".jpg", ".jpeg", ".gif", ".bmp", ".svg"];
-        const AUDIO_FILE_EXTENSIONS: &[&str] =
-            &[".mp3", ".webm", ".wav", ".m4a", ".ogg", ".3gp", ".flac"];
-        const VIDEO_FILE_EXTENSIONS: &[&str] = &[".mp4", ".webm", ".ogv", ".mov", ".mkv"];
-        const PDF_FILE_EXTENSIONS: &[&str] = &[".pdf"];
-
-        for result in WalkDir::new(&self.directory) {
+        const CANVAS_FILE_EXTENSIONS: &[&str] = &[".canvas"];
+        const EXCALIDRAW_FILE_EXTENSIONS: &[&str] = &[".excalidraw.md", ".excalidraw"];
+
+        let walker = WalkDir::new(&self.directory)
+            .into_iter()
+            .filter_entry(|entry| !self.is_excluded(entry.path()));
+
+        for result in walker {
             match result {
                 Ok(entry) => {
                     if !entry.file_type().is_file() {
@@ -47,89 +62,233 @@ impl VaultBuilder {
                     // path relative to vault root directory
                     let relative_path = entry.path().strip_prefix(&self.directory).unwrap();
 
-                    if MARKDOWN_FILE_EXTENSIONS
+                    if EXCALIDRAW_FILE_EXTENSIONS
+                        .iter()
+                        .any(|ext| filename.to_string_lossy().ends_with(ext))
+                    {
+                        // drawings have no tags to match a tag filter
+                        if self.tags.is_some() {
+                            continue;
+                        }
+
+                        match Drawing::from_file(&entry.path()) {
+                            Ok(drawing) => {
+                                // `drawing.excalidraw.md` is linked as `drawing.excalidraw`
+                                let drawing_path = if filename.to_string_lossy().ends_with(".md") {
+                                    ItemPath::from_path_without_ext(relative_path)
+                                } else {
+                                    ItemPath::from_path(relative_path)
+                                };
+                                let index = graph.add_node(drawing_path.clone());
+                                drawings.insert(drawing_path, DrawingItem { index, drawing });
+                            }
+                            Err(err) => {
+                                eprintln!("Unable to parse {}: {}", entry.path().display(), err);
+                                unparsed_notes.push(relative_path.to_path_buf());
+                            }
+                        }
+                    } else if MARKDOWN_FILE_EXTENSIONS
                         .iter()
                         .any(|ext| filename.to_string_lossy().ends_with(ext))
                     {
                         match Note::from_file(&entry.path()) {
                             Ok(note) => {
+                                for warning in note.metadata.warnings() {
+                                    eprintln!("{}: warning: {}", relative_path.display(), warning);
+                                }
                                 if let Some(tags) = &self.tags {
                                     if !note.tags.iter().any(|t| tags.contains(t)) {
                                         continue;
                                     }
                                 }
                                 let note_path = ItemPath::from_path_without_ext(relative_path);
+                                if let Some(schema) = &self.schema {
+                                    for err in schema.validate(&note_path, &note) {
+                                        eprintln!("{}: {}", relative_path.display(), err);
+                                        metadata_errors.push((relative_path.to_path_buf(), err));
+                                    }
+                                }
                                 let index = graph.add_node(note_path.clone());
                                 notes.insert(note_path, NoteItem { index, note });
                             }
                             Err(err) => {
-                                eprintln!("Unable to parse {}: {}", entry.path().display(), err)
+                                eprintln!("Unable to parse {}: {}", entry.path().display(), err);
+                                unparsed_notes.push(relative_path.to_path_buf());
                             }
                         }
-                    } else if IMAGE_FILE_EXTENSIONS
-                        .iter()
-                        .any(|ext| filename.to_string_lossy().ends_with(ext))
-                    {
-                        let item_path = ItemPath::from_path(relative_path);
-                        files.insert(item_path, EmbeddedFile::Image(entry.path().to_path_buf()));
-                    } else if AUDIO_FILE_EXTENSIONS
-                        .iter()
-                        .any(|ext| filename.to_string_lossy().ends_with(ext))
-                    {
-                        let item_path = ItemPath::from_path(relative_path);
-                        files.insert(item_path, EmbeddedFile::Audio(entry.path().to_path_buf()));
-                    } else if VIDEO_FILE_EXTENSIONS
-                        .iter()
-                        .any(|ext| filename.to_string_lossy().ends_with(ext))
-                    {
-                        let item_path = ItemPath::from_path(relative_path);
-                        files.insert(item_path, EmbeddedFile::Video(entry.path().to_path_buf()));
-                    } else if PDF_FILE_EXTENSIONS
+                    } else if CANVAS_FILE_EXTENSIONS
                         .iter()
                         .any(|ext| filename.to_string_lossy().ends_with(ext))
                     {
+                        // canvases have no tags to match a tag filter
+                        if self.tags.is_some() {
+                            continue;
+                        }
+
+                        match Canvas::from_file(&entry.path()) {
+                            Ok(canvas) => {
+                                let canvas_path = ItemPath::from_path(relative_path);
+                                let index = graph.add_node(canvas_path.clone());
+                                canvases.insert(canvas_path, CanvasItem { index, canvas });
+                            }
+                            Err(err) => {
+                                eprintln!("Unable to parse {}: {}", entry.path().display(), err);
+                                unparsed_notes.push(relative_path.to_path_buf());
+                            }
+                        }
+                    } else if !is_hidden(relative_path) {
+                        // any other file is an attachment
                         let item_path = ItemPath::from_path(relative_path);
-                        files.insert(item_path, EmbeddedFile::Pdf(entry.path().to_path_buf()));
+                        files.insert(item_path, EmbeddedFile::new(entry.path()));
                     }
                 }
                 Err(err) => eprintln!("{}", err),
             }
         }
 
-        for item in notes.values() {
+        let node_index = |target: &str| {
+            resolve_link(&notes, target)
+                .map(|(_, item)| item.index)
+                .or_else(|| resolve_link(&canvases, target).map(|(_, item)| item.index))
+                .or_else(|| resolve_link(&drawings, target).map(|(_, item)| item.index))
+        };
+
+        let markdown_link_index = |from: &ItemPath, dest: &str| {
+            resolve_markdown_link(&notes, from, dest)
+                .map(|(_, item)| item.index)
+                .or_else(|| {
+                    resolve_markdown_link(&canvases, from, dest).map(|(_, item)| item.index)
+                })
+                .or_else(|| {
+                    resolve_markdown_link(&drawings, from, dest).map(|(_, item)| item.index)
+                })
+        };
+
+        for (path, item) in notes.iter() {
             for link in item.note.links.iter() {
-                if let Some((found, _)) = resolve_link(&notes, &link.target) {
-                    graph.add_edge(item.index, notes[&found].index, ());
+                if let Some(index) = node_index(&link.target) {
+                    graph.add_edge(item.index, index, ());
+                }
+            }
+
+            for dest in item.note.markdown_links.iter() {
+                if let Some(index) = markdown_link_index(path, dest) {
+                    graph.add_edge(item.index, index, ());
+                }
+            }
+        }
+
+        for item in canvases.values() {
+            for link in item.canvas.links().iter() {
+                if let Some(index) = node_index(&link.target) {
+                    graph.add_edge(item.index, index, ());
+                }
+            }
+
+            for (from, to) in item.canvas.file_edges() {
+                if let (Some(from), Some(to)) = (node_index(&from), node_index(&to)) {
+                    graph.add_edge(from, to, ());
+                }
+            }
+        }
+
+        for item in drawings.values() {
+            for link in item.drawing.links().iter() {
+                if let Some(index) = node_index(&link.target) {
+                    graph.add_edge(item.index, index, ());
                 }
             }
         }
 
         Vault {
             notes,
+            canvases,
+            drawings,
             graph,
             files,
-            root: self.directory,
+            unparsed_notes,
+            metadata_errors,
         }
     }
 
+    /// Skip the files in `directory`, such as the output of a previous build
+    /// inside the vault.
+    pub(crate) fn exclude_directory<P: AsRef<Path>>(&mut self, directory: P) -> &mut Self {
+        let (Ok(vault), Ok(directory)) = (
+            self.directory.canonicalize(),
+            directory.as_ref().canonicalize(),
+        ) else {
+            return self;
+        };
+        // directories outside the vault are never walked
+        if let Ok(relative_path) = directory.strip_prefix(vault) {
+            self.excluded.push(relative_path.to_path_buf());
+        }
+        self
+    }
+
+    fn is_excluded(&self, path: &Path) -> bool {
+        path.strip_prefix(&self.directory)
+            .map(|path| self.excluded.iter().any(|excluded| path == excluded))
+            .unwrap_or(false)
+    }
+
     pub(crate) fn filter_tags(&mut self, tags: Vec<String>) -> &mut Self {
         self.tags = Some(tags);
         self
     }
+
+    /// Check the metadata of notes against `schema`, reporting the errors.
+    pub(crate) fn metadata_schema(&mut self, schema: MetadataSchema) -> &mut Self {
+        if !schema.is_empty() {
+            self.schema = Some(schema);
+        }
+        self
+    }
 }
 
 pub(crate) struct Vault {
     pub notes: HashMap<ItemPath, NoteItem>,
+    pub canvases: HashMap<ItemPath, CanvasItem>,
+    pub drawings: HashMap<ItemPath, DrawingItem>,
     graph: StableGraph<ItemPath, ()>,
-    pub(crate) root: PathBuf,
     pub(crate) files: HashMap<ItemPath, EmbeddedFile>,
+
+    /// Notes that could not be read, relative to the vault.
+    pub(crate) unparsed_notes: Vec<PathBuf>,
+
+    /// Errors of notes' metadata against the schema, by note file.
+    pub(crate) metadata_errors: Vec<(PathBuf, SchemaError)>,
 }
 
 impl Vault {
+    /// Number of notes that could not be read and of metadata errors
+    /// against the schema.
+    pub(crate) fn problems(&self) -> usize {
+        self.unparsed_notes.len() + self.metadata_errors.len()
+    }
+
     pub(crate) fn get_note(&self, note_path: &ItemPath) -> Option<&Note> {
         self.notes.get(note_path).map(|item| &item.note)
     }
 
+    pub(crate) fn get_canvas(&self, canvas_path: &ItemPath) -> Option<&Canvas> {
+        self.canvases.get(canvas_path).map(|item| &item.canvas)
+    }
+
+    pub(crate) fn get_drawing(&self, drawing_path: &ItemPath) -> Option<&Drawing> {
+        self.drawings.get(drawing_path).map(|item| &item.drawing)
+    }
+
+    /// Graph node of a note, canvas or drawing.
+    fn node_index(&self, path: &ItemPath) -> Option<NodeIndex> {
+        self.notes
+            .get(path)
+            .map(|item| item.index)
+            .or_else(|| self.canvases.get(path).map(|item| item.index))
+            .or_else(|| self.drawings.get(path).map(|item| item.index))
+    }
+
     pub(crate) fn local_graph(
         &self,
         path: &ItemPath,
@@ -155,7 +314,12 @@ impl Vault {
                 .entry(node.clone())
                 .or_insert_with(|| g.add_node(node.clone()));
 
-            for succ in self.graph.neighbors(self.notes[&node].index) {
+            let index = match self.node_index(&node) {
+                Some(index) => index,
+                None => continue,
+            };
+
+            for succ in self.graph.neighbors(index) {
                 let succ_path = &self.graph[succ];
                 stack.push_back(succ_path.clone());
 
@@ -172,9 +336,57 @@ impl Vault {
         Some(g)
     }
 
+    /// Resolve a link to a note, a canvas or a drawing.
     pub(crate) fn resolve_link<S: Into<String>>(&self, target: S) -> Option<ItemPath> {
-        let (item_path, _) = resolve_link(&self.notes, target)?;
-        Some(item_path)
+        let target = target.into();
+        resolve_link(&self.notes, target.clone())
+            .map(|(item_path, _)| item_path)
+            .or_else(|| {
+                self.resolve_canvas_link(target.clone())
+                    .map(|(item_path, _)| item_path)
+            })
+            .or_else(|| {
+                self.resolve_drawing_link(target)
+                    .map(|(item_path, _)| item_path)
+            })
+    }
+
+    pub(crate) fn resolve_canvas_link<S: Into<String>>(
+        &self,
+        target: S,
+    ) -> Option<(ItemPath, &Canvas)> {
+        resolve_link(&self.canvases, target).map(|(item_path, item)| (item_path, &item.canvas))
+    }
+
+    pub(crate) fn resolve_drawing_link<S: Into<String>>(
+        &self,
+        target: S,
+    ) -> Option<(ItemPath, &Drawing)> {
+        resolve_link(&self.drawings, target).map(|(item_path, item)| (item_path, &item.drawing))
+    }
+
+    /// Resolve the `file` of a canvas file node to a note, canvas or file.
+    pub(crate) fn resolve_canvas_file(&self, file: &str) -> Option<ItemPath> {
+        self.resolve_link(file_target(file)).or_else(|| {
+            self.resolve_embedded_link(file)
+                .map(|(item_path, _)| item_path)
+        })
+    }
+
+    /// Resolve the destination of a markdown link or image in the item at
+    /// `from` to a note, canvas, drawing or file.
+    pub(crate) fn resolve_markdown_link(&self, from: &ItemPath, dest: &str) -> Option<ItemPath> {
+        resolve_markdown_link(&self.notes, from, dest)
+            .map(|(item_path, _)| item_path)
+            .or_else(|| {
+                resolve_markdown_link(&self.canvases, from, dest).map(|(item_path, _)| item_path)
+            })
+            .or_else(|| {
+                resolve_markdown_link(&self.drawings, from, dest).map(|(item_path, _)| item_path)
+            })
+            .or_else(|| {
+                resolve_markdown_link(&self.files, from, dest).map(|(item_path, _)| item_path)
+            })
     }
 
     pub(crate) fn resolve_embedded_link<S: Into<String>>(
@@ -185,6 +397,18 @@ impl Vault {
     }
 }
 
+/// A `Canvas` in a `Vault`.
+pub(crate) struct CanvasItem {
+    pub canvas: Canvas,
+    index: NodeIndex,
+}
+
+/// A `Drawing` in a `Vault`.
+pub(crate) struct DrawingItem {
+    pub drawing: Drawing,
+    index: NodeIndex,
+}
+
 /// A `Note` in a `Vault`.
 pub(crate) struct NoteItem {
     pub note: Note,
@@ -221,6 +445,14 @@ impl ItemPath {
 
         ItemPath::Absolute(parts)
     }
+
+    /// Last component of the path.
+    pub(crate) fn file_name(&self) -> Option<&str> {
+        match self {
+            ItemPath::Absolute(components) => components.last().map(String::as_str),
+            ItemPath::FileName(filename) => Some(filename),
+        }
+    }
 }
 
 impl From<ItemPath> for PathBuf {
@@ -285,9 +517,234 @@ pub(crate) fn resolve_link<S: Into<String>, V>(
     }
 }
 
-pub(crate) enum EmbeddedFile {
-    Image(PathBuf),
-    Audio(PathBuf),
-    Video(PathBuf),
-    Pdf(PathBuf),
+/// Resolve a markdown link destination written in the item at `from`.
+///
+/// Destinations are relative to the item, or to the vault root when they
+/// start with `/`. As Obsidian writes links with the shortest unambiguous
+/// path by default, a destination that matches no path is looked up by its
+/// file name.
+pub(crate) fn resolve_markdown_link<'a, V>(
+    paths: &'a HashMap<ItemPath, V>,
+    from: &ItemPath,
+    dest: &str,
+) -> Option<(ItemPath, &'a V)> {
+    // URLs are left as written, even when their path matches a file name
+    if !is_local_link(dest) {
+        return None;
+    }
+
+    let target = markdown_link_path(from, dest)?;
+    if let Some(value) = paths.get(&target) {
+        return Some((target, value));
+    }
+
+    match &target {
+        ItemPath::Absolute(components) => resolve_link(paths, components.last()?.clone()),
+        ItemPath::FileName(_) => None,
+    }
+}
+
+/// Vault path of a markdown link destination, without its fragment and,
+/// for notes, its `.md` extension. `None` for a destination that is only a
+/// fragment, such as `#Heading`, which links to the same page.
+fn markdown_link_path(from: &ItemPath, dest: &str) -> Option<ItemPath> {
+    let path = dest.split(['#', '?']).next().unwrap_or_default();
+    if path.is_empty() {
+        return None;
+    }
+    let path = percent_decode(path);
+    let path = path.strip_suffix(".md").unwrap_or(&path);
+
+    let mut components: Vec<String> = match from {
+        ItemPath::Absolute(components) if !path.starts_with('/') => {
+            components[..components.len().saturating_sub(1)].to_vec()
+        }
+        _ => Vec::new(),
+    };
+
+    for part in path.split('/') {
+        match part {
+            "" | "." => {}
+            ".." => {
+                components.pop();
+            }
+            part => components.push(part.to_string()),
+        }
+    }
+
+    if components.is_empty() {
+        None
+    } else {
+        Some(ItemPath::Absolute(components))
+    }
+}
+
+fn percent_decode(value: &str) -> String {
+    let mut bytes = Vec::with_capacity(value.len());
+    let mut i = 0;
+
+    while i < value.len() {
+        let byte = value.as_bytes()[i];
+        if byte == b'%' {
+            if let Some(decoded) = value
+                .get(i + 1..i + 3)
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                bytes.push(decoded);
+                i += 3;
+                continue;
+            }
+        }
+        bytes.push(byte);
+        i += 1;
+    }
+
+    String::from_utf8_lossy(&bytes).to_string()
+}
+
+/// Whether a path is in, or is, a hidden file or directory, such as the
+/// `.obsidian` and `.garden` settings.
+fn is_hidden(relative_path: &Path) -> bool {
+    relative_path
+        .components()
+        .any(|component| component.as_os_str().to_string_lossy().starts_with('.'))
+}
+
+/// An attachment: a file in a `Vault` that is not a note, canvas or drawing.
+pub(crate) struct EmbeddedFile {
+    path: PathBuf,
+}
+
+impl EmbeddedFile {
+    pub(crate) fn new<P: AsRef<Path>>(path: P) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+        }
+    }
+
+    /// Path of the file on disk.
+    pub(crate) fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Extension of the file, which its type is known by.
+    pub(crate) fn extension(&self) -> String {
+        self.path
+            .extension()
+            .map(|extension| extension.to_string_lossy().to_lowercase())
+            .unwrap_or_default()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{markdown_link_path, resolve_markdown_link, ItemPath, VaultBuilder};
+    use std::collections::HashMap;
+
+    fn path(value: &str) -> ItemPath {
+        ItemPath::Absolute(value.split('/').map(|v| v.to_string()).collect())
+    }
+
+    #[test]
+    fn test_markdown_link_path_relative() {
+        let from = path("notes/daily/Today");
+        assert_eq!(
+            markdown_link_path(&from, "Other%20Note.md#Intro"),
+            Some(path("notes/daily/Other Note"))
+        );
+        assert_eq!(
+            markdown_link_path(&from, "../../attachments/pic.png"),
+            Some(path("attachments/pic.png"))
+        );
+    }
+
+    #[test]
+    fn test_markdown_link_path_absolute() {
+        let from = path("notes/Today");
+        assert_eq!(
+            markdown_link_path(&from, "/attachments/pic%20one.png"),
+            Some(path("attachments/pic one.png"))
+        );
+        assert_eq!(markdown_link_path(&from, "/"), None);
+    }
+
+    #[test]
+    fn test_markdown_link_path_fragment() {
+        let from = path("Projects/Plan");
+        assert_eq!(markdown_link_path(&from, "#Plan"), None);
+        assert_eq!(markdown_link_path(&from, "?x=1"), None);
+        assert_eq!(
+            markdown_link_path(&from, "Other.md#Plan"),
+            Some(path("Projects/Other"))
+        );
+    }
+
+    #[test]
+    fn test_resolve_markdown_link_urls() {
+        let mut notes = HashMap::new();
+        notes.insert(path("Foo"), ());
+        notes.insert(path("Projects"), ());
+        let from = path("Projects/Plan");
+
+        assert_eq!(
+            resolve_markdown_link(&notes, &from, "../Foo.md"),
+            Some((path("Foo"), &()))
+        );
+        // not matched by file name
+        assert_eq!(
+            resolve_markdown_link(&notes, &from, "https://example.com/Foo"),
+            None
+        );
+        assert_eq!(
+            resolve_markdown_link(&notes, &from, "//example.com/Foo"),
+            None
+        );
+        assert_eq!(resolve_markdown_link(&notes, &from, "mailto:Foo"), None);
+        // not the folder note of the note's folder
+        assert_eq!(resolve_markdown_link(&notes, &from, "#Plan"), None);
+    }
+
+    #[test]
+    fn test_exclude_directory() {
+        let dir = std::env::temp_dir().join(format!("garden-exclude-{}", std::process::id()));
+        std::fs::create_dir_all(dir.join("dist")).unwrap();
+        std::fs::write(dir.join("Note.md"), "text").unwrap();
+        std::fs::write(dir.join("dist/Old.md"), "text").unwrap();
+        std::fs::write(dir.join("dist.md"), "text").unwrap();
+
+        let mut builder = VaultBuilder::new(&dir);
+        builder.exclude_directory(dir.join("dist"));
+        let vault = builder.build();
+
+        let mut notes: Vec<String> = vault.notes.keys().map(ToString::to_string).collect();
+        notes.sort();
+        assert_eq!(notes, vec!["Note", "dist"]);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_unparsed_notes() {
+        let dir = std::env::temp_dir().join(format!("garden-unparsed-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("Note.md"), "text").unwrap();
+        std::fs::write(dir.join("Broken.md"), "---\ntags: [a, b\n---\ntext").unwrap();
+        std::fs::write(dir.join("Board.canvas"), "{ not json").unwrap();
+
+        let vault = VaultBuilder::new(&dir).build();
+        assert_eq!(vault.problems(), 2);
+
+        let mut unparsed = vault.unparsed_notes.clone();
+        unparsed.sort();
+        assert_eq!(
+            unparsed,
+            vec![
+                std::path::PathBuf::from("Board.canvas"),
+                std::path::PathBuf::from("Broken.md")
+            ]
+        );
+        assert_eq!(vault.notes.len(), 1);
+
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
 }
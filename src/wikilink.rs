@@ -1,6 +1,8 @@
-use pulldown_cmark::CowStr;
+use pulldown_cmark::{Event, Options, Parser};
 use serde::Serialize;
-use std::fmt::Display;
+use std::{fmt::Display, ops::Range};
+
+use crate::obsidian::code_ranges;
 
 #[derive(Clone, PartialEq, Debug, Default, Serialize)]
 pub struct Wikilink {
@@ -27,154 +29,358 @@ impl Wikilink {
     }
 }
 
-impl Display for Wikilink {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        if self.embedded {
-            f.write_fmt(format_args!("![[{}]]", self.target))
-        } else {
-            match &self.label {
-                Some(text) => f.write_fmt(format_args!("[[{}|{}]]", self.target, &text)),
-                None => f.write_fmt(format_args!("[[{}]]", self.target)),
+impl Wikilink {
+    /// Alt text and size given in the label of an embed, as in
+    /// `![[pic.png|300]]`, `![[pic.png|300x200]]` or `![[pic.png|alt|300]]`.
+    pub fn embed_options(&self) -> EmbedOptions {
+        let mut options = EmbedOptions::default();
+        let label = match &self.label {
+            Some(label) => label,
+            None => return options,
+        };
+
+        let (alt, size) = match label.rsplit_once('|') {
+            Some((alt, size)) => (Some(alt), size),
+            None => (None, label.as_str()),
+        };
+
+        match parse_size(size.trim()) {
+            Some((width, height)) => {
+                options.width = Some(width);
+                options.height = height;
+                options.alt = alt.map(|alt| alt.to_string());
             }
+            None => options.alt = Some(label.to_string()),
         }
+
+        options
     }
 }
 
-pub struct WikilinkParser {
-    state: WikilinkParserState,
-    current_value: Option<Wikilink>,
-    embedded: bool,
+/// Alt text and size of an embedded file.
+#[derive(Clone, PartialEq, Debug, Default)]
+pub struct EmbedOptions {
+    pub alt: Option<String>,
+    pub width: Option<u32>,
+    pub height: Option<u32>,
 }
 
-impl Default for WikilinkParser {
-    fn default() -> Self {
-        Self::new()
+/// `300` or `300x200`.
+fn parse_size(size: &str) -> Option<(u32, Option<u32>)> {
+    match size.split_once('x') {
+        Some((width, height)) => Some((width.parse().ok()?, Some(height.parse().ok()?))),
+        None => Some((size.parse().ok()?, None)),
     }
 }
 
-impl WikilinkParser {
-    pub fn new() -> Self {
+impl Display for Wikilink {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.embedded {
+            f.write_str("!")?;
+        }
+        match &self.label {
+            Some(text) => f.write_fmt(format_args!("[[{}|{}]]", self.target, &text)),
+            None => f.write_fmt(format_args!("[[{}]]", self.target)),
+        }
+    }
+}
+
+/// Finds the wikilinks written in a markdown document, with the byte range
+/// each one spans in the source.
+///
+/// Wikilinks are read from the raw source rather than from parsed text
+/// events, so escapes, entities, emphasis or brackets inside a link do not
+/// split it. Links inside code spans and code blocks are skipped, as are
+/// `\[[` escaped brackets and links broken across lines.
+pub struct WikilinkParser<'a> {
+    source: &'a str,
+    code: Vec<Range<usize>>,
+    pos: usize,
+}
+
+impl<'a> WikilinkParser<'a> {
+    pub fn new(source: &'a str) -> Self {
         Self {
-            state: WikilinkParserState::Start,
-            current_value: None,
-            embedded: false,
+            source,
+            code: code_ranges(source),
+            pos: 0,
         }
     }
 
-    pub fn feed(&mut self, text: &CowStr) -> Option<Wikilink> {
-        match (&self.state, text) {
-            (WikilinkParserState::Start, CowStr::Borrowed("![")) => {
-                self.transit_state(WikilinkParserState::FirstOpen);
-                self.embedded = true;
-                None
-            }
-            (WikilinkParserState::Start, CowStr::Borrowed("[")) => {
-                self.transit_state(WikilinkParserState::FirstOpen);
-                None
-            }
-            (WikilinkParserState::FirstOpen, CowStr::Borrowed("[")) => {
-                self.transit_state(WikilinkParserState::SecondOpen);
-                None
-            }
-            (WikilinkParserState::SecondOpen, text) => {
-                let wikilink = if !self.embedded {
-                    parse_wikilink_text(text)
-                } else {
-                    Wikilink::embedded(text.to_string())
-                };
-                self.current_value = Some(wikilink);
-                self.transit_state(WikilinkParserState::Text);
-                None
-            }
-            (WikilinkParserState::Text, CowStr::Borrowed("]")) => {
-                self.transit_state(WikilinkParserState::FirstClose);
-                None
+    fn in_code(&self, pos: usize) -> bool {
+        self.code.iter().any(|range| range.contains(&pos))
+    }
+
+    fn is_escaped(&self, pos: usize) -> bool {
+        let backslashes = self.source[..pos]
+            .bytes()
+            .rev()
+            .take_while(|&byte| byte == b'\\')
+            .count();
+        backslashes % 2 == 1
+    }
+}
+
+impl Iterator for WikilinkParser<'_> {
+    type Item = (Range<usize>, Wikilink);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some(found) = self.source[self.pos..].find(OPEN) {
+            let open = self.pos + found;
+            let text_start = open + OPEN.len();
+            self.pos = text_start;
+
+            if self.in_code(open) || self.is_escaped(open) {
+                continue;
             }
-            (WikilinkParserState::FirstClose, CowStr::Borrowed("]")) => {
-                self.transit_state(WikilinkParserState::Start);
-                self.current_value.clone()
+
+            let rest = &self.source[text_start..];
+            let close = rest.find(CLOSE)?;
+            let text = &rest[..close];
+
+            // a nested `[[` starts the link over, a line break ends it
+            if text.contains(OPEN) || text.contains('\n') || text.trim().is_empty() {
+                continue;
             }
+
+            let embedded = open > 0 && self.source.as_bytes()[open - 1] == b'!';
+            let start = if embedded { open - 1 } else { open };
+            let end = text_start + close + CLOSE.len();
+            self.pos = end;
+
+            let mut wikilink = parse_wikilink_text(text);
+            wikilink.embedded = embedded;
+
+            return Some((start..end, wikilink));
+        }
+
+        None
+    }
+}
+
+const OPEN: &str = "[[";
+const CLOSE: &str = "]]";
+
+/// Events of a markdown document, with the events written within each
+/// wikilink replaced by the events `replace` returns for it.
+///
+/// `replace` is given the wikilink and its source text. Text events that
+/// only partly overlap a wikilink keep the text around it.
+pub(crate) fn parse_with_wikilinks<'a>(
+    source: &'a str,
+    replace: impl FnMut(&Wikilink, &'a str) -> Vec<Event<'a>>,
+) -> Vec<Event<'a>> {
+    parse_with_wikilinks_map(source, replace, |event| event)
+}
+
+/// Like `parse_with_wikilinks`, with the events of the markdown itself, but
+/// not the ones `replace` returns, passed through `map`.
+pub(crate) fn parse_with_wikilinks_map<'a>(
+    source: &'a str,
+    mut replace: impl FnMut(&Wikilink, &'a str) -> Vec<Event<'a>>,
+    mut map: impl FnMut(Event<'a>) -> Event<'a>,
+) -> Vec<Event<'a>> {
+    let mut wikilinks = WikilinkParser::new(source).peekable();
+    let mut output = Vec::new();
+    let mut replaced: Option<Range<usize>> = None;
+
+    for (event, range) in Parser::new_ext(source, Options::all()).into_offset_iter() {
+        // links that end before this event are done
+        while matches!(wikilinks.peek(), Some((span, _)) if span.end <= range.start) {
+            wikilinks.next();
+        }
+
+        let span = match wikilinks.peek() {
+            Some((span, _)) if span.start < range.end => span.clone(),
             _ => {
-                self.transit_state(WikilinkParserState::Start);
-                None
+                output.push(map(event));
+                continue;
             }
+        };
+
+        let within = range.start >= span.start && range.end <= span.end;
+        let is_text = matches!(event, Event::Text(_));
+        if (!within || range == span) && !is_text {
+            // a container that holds the link, or starts or ends inside it
+            output.push(map(event));
+            continue;
+        }
+
+        if !within && range.start < span.start {
+            output.push(Event::Text(source[range.start..span.start].into()));
         }
-    }
 
-    fn transit_state(&mut self, state: WikilinkParserState) {
-        self.state = state;
+        if replaced.as_ref() != Some(&span) {
+            let (_, wikilink) = wikilinks.peek().unwrap();
+            output.extend(replace(wikilink, &source[span.clone()]));
+            replaced = Some(span.clone());
+        }
+
+        if !within && range.end > span.end {
+            output.push(Event::Text(source[span.end..range.end].into()));
+            wikilinks.next();
+        }
     }
+
+    output
 }
 
 fn parse_wikilink_text(text: &str) -> Wikilink {
     let mut split = text.splitn(2, '|');
-    let target = split.next().unwrap().to_string();
+    let target = split.next().unwrap();
+    // `\|` separates the label in links written inside tables
+    let target = target.strip_suffix('\\').unwrap_or(target).to_string();
     let label = split.next().map(|s| s.to_string());
 
     Wikilink::new(target, label)
 }
 
-enum WikilinkParserState {
-    Start,
-    FirstOpen,
-    SecondOpen,
-    Text,
-    FirstClose,
-}
-
 #[cfg(test)]
 mod tests {
-    use super::{Wikilink, WikilinkParser};
-    use crate::wikilink::WikilinkParserState;
-    use pulldown_cmark::CowStr;
+    use super::{parse_with_wikilinks, EmbedOptions, Wikilink, WikilinkParser};
+    use pulldown_cmark::Event;
+
+    fn parse(source: &str) -> Vec<Wikilink> {
+        WikilinkParser::new(source).map(|(_, link)| link).collect()
+    }
 
     #[test]
     fn test_parse_wikilink() {
-        let mut parser = WikilinkParser::new();
-        assert_eq!(parser.feed(&CowStr::Borrowed("[")), None,);
-        assert_eq!(parser.feed(&CowStr::Borrowed("[")), None,);
-        assert_eq!(parser.feed(&CowStr::Borrowed("Page One")), None,);
-        assert_eq!(parser.feed(&CowStr::Borrowed("]")), None,);
+        assert_eq!(parse("[[Page One]]"), vec![Wikilink::new("Page One", None)]);
+    }
+
+    #[test]
+    fn test_parse_wikilink_label() {
+        assert_eq!(
+            parse("[[Page One|Label 1]]"),
+            vec![Wikilink::new("Page One", Some("Label 1"))]
+        );
         assert_eq!(
-            parser.feed(&CowStr::Borrowed("]")),
-            Some(Wikilink::new("Page One", None))
+            parse("[[Page|a | b]]"),
+            vec![Wikilink::new("Page", Some("a | b"))]
         );
     }
 
     #[test]
-    fn test_parse_wikilink_label() {
-        let mut parser = WikilinkParser::new();
-        assert_eq!(parser.feed(&CowStr::Borrowed("[")), None,);
-        assert_eq!(parser.feed(&CowStr::Borrowed("[")), None,);
-        assert_eq!(parser.feed(&CowStr::Borrowed("Page One|Label 1")), None,);
-        assert_eq!(parser.feed(&CowStr::Borrowed("]")), None,);
+    fn test_parse_wikilink_embed() {
+        let mut embed = Wikilink::embedded("test.webp");
+        assert_eq!(parse("![[test.webp]]"), vec![embed.clone()]);
+
+        embed.label = Some("300".to_string());
+        assert_eq!(parse("![[test.webp|300]]"), vec![embed]);
+    }
+
+    #[test]
+    fn test_embed_options() {
+        let options = |label: &str| {
+            let mut embed = Wikilink::embedded("pic.png");
+            embed.label = Some(label.to_string());
+            embed.embed_options()
+        };
+
+        assert_eq!(
+            Wikilink::embedded("pic.png").embed_options(),
+            EmbedOptions::default()
+        );
+        assert_eq!(
+            options("300"),
+            EmbedOptions {
+                width: Some(300),
+                ..Default::default()
+            }
+        );
+        assert_eq!(
+            options("300x200"),
+            EmbedOptions {
+                width: Some(300),
+                height: Some(200),
+                ..Default::default()
+            }
+        );
         assert_eq!(
-            parser.feed(&CowStr::Borrowed("]")),
-            Some(Wikilink::new("Page One", Some("Label 1")))
+            options("A cat|300"),
+            EmbedOptions {
+                alt: Some("A cat".to_string()),
+                width: Some(300),
+                height: None,
+            }
+        );
+        assert_eq!(
+            options("A cat, 3x zoom"),
+            EmbedOptions {
+                alt: Some("A cat, 3x zoom".to_string()),
+                ..Default::default()
+            }
         );
     }
 
     #[test]
-    fn test_parse_wikilink_reset_state_if_an_unexpected_token_is_found() {
-        let mut parser = WikilinkParser::new();
-        assert_eq!(parser.feed(&CowStr::Borrowed("[")), None,);
-        assert_eq!(parser.feed(&CowStr::Borrowed("[")), None,);
-        assert_eq!(parser.feed(&CowStr::Borrowed("Page One|Label 1")), None,);
-        assert_eq!(parser.feed(&CowStr::Borrowed("]")), None,);
-        assert_eq!(parser.feed(&CowStr::Borrowed(" Oops")), None);
-        assert!(matches!(parser.state, WikilinkParserState::Start));
+    fn test_parse_wikilink_spans() {
+        let source = "[[a]] [[b]] and ![[c]]";
+        let spans: Vec<_> = WikilinkParser::new(source)
+            .map(|(span, _)| &source[span])
+            .collect();
+        assert_eq!(spans, vec!["[[a]]", "[[b]]", "![[c]]"]);
     }
 
     #[test]
-    fn test_parse_wikilink_embed() {
-        let mut parser = WikilinkParser::new();
-        assert_eq!(parser.feed(&CowStr::Borrowed("![")), None);
-        assert_eq!(parser.feed(&CowStr::Borrowed("[")), None);
-        assert_eq!(parser.feed(&CowStr::Borrowed("test.webp")), None);
-        assert_eq!(parser.feed(&CowStr::Borrowed("]")), None);
+    fn test_parse_wikilink_after_embed() {
+        assert_eq!(
+            parse("![[test.webp]] [[Page]]"),
+            vec![Wikilink::embedded("test.webp"), Wikilink::new("Page", None)]
+        );
+    }
+
+    #[test]
+    fn test_parse_wikilink_with_escapes_and_entities() {
+        assert_eq!(
+            parse("[[a\\_b]] [[c &amp; d]] [[*e*]]"),
+            vec![
+                Wikilink::new("a\\_b", None),
+                Wikilink::new("c &amp; d", None),
+                Wikilink::new("*e*", None),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_parse_wikilink_with_brackets() {
+        assert_eq!(parse("[[a [b] c]]"), vec![Wikilink::new("a [b] c", None)]);
+        assert_eq!(parse("[[a [[b]]"), vec![Wikilink::new("b", None)]);
+    }
+
+    #[test]
+    fn test_parse_wikilink_in_table() {
+        assert_eq!(
+            parse("| [[Page\\|Label]] |"),
+            vec![Wikilink::new("Page", Some("Label"))]
+        );
+    }
+
+    #[test]
+    fn test_parse_wikilink_ignored() {
+        assert!(parse("`[[a]]`").is_empty());
+        assert!(parse("```\n[[a]]\n```").is_empty());
+        assert!(parse("\\[[a]]").is_empty());
+        assert!(parse("[[a\nb]]").is_empty());
+        assert!(parse("[[a").is_empty());
+        assert!(parse("[[]]").is_empty());
+    }
+
+    #[test]
+    fn test_parse_with_wikilinks() {
+        let events = parse_with_wikilinks("x [[a|**b**]] y", |link, text| {
+            vec![Event::Html(format!("{}={}", link.target, text).into())]
+        });
         assert_eq!(
-            parser.feed(&CowStr::Borrowed("]")),
-            Some(Wikilink::embedded("test.webp"))
+            events,
+            vec![
+                Event::Start(pulldown_cmark::Tag::Paragraph),
+                Event::Text("x ".into()),
+                Event::Html("a=[[a|**b**]]".into()),
+                Event::Text(" y".into()),
+                Event::End(pulldown_cmark::Tag::Paragraph),
+            ]
         );
     }
 }
@@ -157,4 +157,30 @@ export const initGraph = (currentPath, graph) => {
   })
 
   return svg
-}
\ No newline at end of file
+}
+
+export const sortableTable = (tableEl) => {
+  const tbody = tableEl.querySelector("tbody")
+  const headers = [...tableEl.querySelectorAll("th")]
+
+  headers.forEach((th, column) => {
+    if (!th.dataset.sort) {
+      return
+    }
+
+    th.addEventListener("click", () => {
+      const ascending = th.getAttribute("aria-sort") !== "ascending"
+      headers.forEach(header => header.removeAttribute("aria-sort"))
+      th.setAttribute("aria-sort", ascending ? "ascending" : "descending")
+
+      const rows = [...tbody.querySelectorAll("tr")]
+      rows.sort((a, b) => {
+        // cells can sort by a value other than their text, such as a date
+        const x = a.children[column].dataset.value ?? a.children[column].innerText
+        const y = b.children[column].dataset.value ?? b.children[column].innerText
+        return (ascending ? 1 : -1) * x.localeCompare(y, undefined, {numeric: true})
+      })
+      tbody.append(...rows)
+    })
+  })
+}